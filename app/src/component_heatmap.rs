@@ -0,0 +1,79 @@
+use yew::prelude::*;
+
+
+////////////////////////////////////////////////////////////
+/// Color for a heatmap cell; 0 is white, 1 is full red
+pub fn heat_color(v: f64) -> String {
+    let v = v.clamp(0.0, 1.0);
+    let gb = (255.0 * (1.0 - v)) as u8;
+    format!("#FF{:02X}{:02X}", gb, gb)
+}
+
+
+////////////////////////////////////////////////////////////
+/// Draw a heatmap as SVG. Values are expected in the range 0..1.
+/// Cell text is shown inside each cell, and as a tooltip
+pub fn svg_heatmap(
+    row_names: &[String],
+    col_names: &[String],
+    values: &[Vec<f64>],
+    cell_text: &[Vec<String>]
+) -> Html {
+
+    let cell_w = 40.0;
+    let cell_h = 16.0;
+
+    //Space for labels, roughly based on the longest name
+    let label_len = |list: &[String]| list.iter().map(|s| s.len()).max().unwrap_or(0) as f64;
+    let margin_left = 10.0 + 6.0 * label_len(row_names);
+    let margin_top = 10.0 + 4.5 * label_len(col_names);
+
+    let total_w = margin_left + cell_w * col_names.len() as f64 + 10.0;
+    let total_h = margin_top + cell_h * row_names.len() as f64 + 10.0;
+
+    let mut outp = Vec::new();
+
+    //Column labels, rotated
+    for (j, name) in col_names.iter().enumerate() {
+        let x = margin_left + cell_w * (j as f64 + 0.5);
+        outp.push(html! {
+            <text style="font-size:10px;font-family:sans-serif;" transform={format!("translate({},{}) rotate(-45)", x, margin_top - 4.0)}>
+                {name.clone()}
+            </text>
+        });
+    }
+
+    for (i, name) in row_names.iter().enumerate() {
+        let y = margin_top + cell_h * i as f64;
+
+        //Row label
+        outp.push(html! {
+            <text style="font-size:10px;font-family:sans-serif;" text-anchor="end" x={format!("{}", margin_left - 4.0)} y={format!("{}", y + cell_h*0.75)}>
+                {name.clone()}
+            </text>
+        });
+
+        //Cells
+        for (j, col_name) in col_names.iter().enumerate() {
+            let x = margin_left + cell_w * j as f64;
+            let v = values[i][j];
+            let txt = cell_text.get(i).and_then(|r| r.get(j)).cloned().unwrap_or_default();
+            outp.push(html! {
+                <g>
+                    <rect x={format!("{}", x)} y={format!("{}", y)} width={format!("{}", cell_w)} height={format!("{}", cell_h)} fill={heat_color(v)} stroke="white" stroke-width="0.5">
+                        <title>{format!("{} / {}: {}", name, col_name, txt)}</title>
+                    </rect>
+                    <text style="font-size:8px;font-family:sans-serif;pointer-events:none;" text-anchor="middle" x={format!("{}", x + cell_w/2.0)} y={format!("{}", y + cell_h*0.7)}>
+                        {txt}
+                    </text>
+                </g>
+            });
+        }
+    }
+
+    html! {
+        <svg width={format!("{}", total_w)} height={format!("{}", total_h)} viewBox={format!("0 0 {} {}", total_w, total_h)} xmlns="http://www.w3.org/2000/svg">
+            { outp }
+        </svg>
+    }
+}
@@ -24,19 +24,51 @@ impl Color {
 /// x
 #[derive(Debug)]
 pub struct GeoStats {
-    minx: f64,
-    miny: f64,
-    maxx: f64,
-    maxy: f64,
+    pub minx: f64,
+    pub miny: f64,
+    pub maxx: f64,
+    pub maxy: f64,
 }
 
+////////////////////////////////////////////////////////////
+/// What to draw for each country
+struct MapShading {
+    counts: HashMap<String,i32>,
+    scaled: HashMap<String,f64>,
+    on_click: Option<Callback<String>>,
+    /// Only draw country borders, as a background for other layers
+    outline_only: bool,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Color for a scaled value 0..1
+fn shade_color(v: f64) -> String {
+    let color = Color {red: (v * 255_f64) as u8, blue: 0, green: 0};
+    color.as_string()
+}
+
+
+////////////////////////////////////////////////////////////
+/// Function to scale the values
+fn transform_scale(v:i32) -> f64 {
+    ((v + 1) as f64).log10()
+}
+
+
+/// Size of the legend in its own units; it is scaled to fit the map
+const LEGEND_W: f64 = 320.0;
+const LEGEND_H: f64 = 28.0;
+
+
 pub struct GeoMapView {
 }
 impl GeoMapView {
 
     ////////////////////////////////////////////////////////////
-    /// x
-    pub fn draw_geojson(geojson: &GeoJson, hist: &Vec<(String,i32)>) -> Html {
+    /// Draw countries shaded by count, on a log scale, with a legend. Hovering a country shows its count.
+    /// The ISO 3166 alpha-3 code of a clicked country is sent to on_click
+    pub fn draw_geojson(geojson: &GeoJson, hist: &Vec<(String,i32)>, on_click: Option<Callback<String>>) -> Html {
 
         //Place to store the extent of the map
         let mut geostat = GeoStats {
@@ -46,30 +78,100 @@ impl GeoMapView {
             maxy:0 as f64
         };
 
-        //Function to scale the values
-        fn transform_scale(v:i32) -> f64 {
-            ((v + 1) as f64).log10()
-        }
-
         //Figure out the largest value for normalization
         let max_cnt = *hist.iter().map(|(_,v)| v).max().unwrap_or(&666);
         let transformed_max_cnt = transform_scale(max_cnt);//((max_cnt + 1) as f64).log10();
 
         //Transform all values, store in map
-        let mut map_hist = HashMap::new();
+        let mut shading = MapShading {
+            counts: HashMap::new(),
+            scaled: HashMap::new(),
+            on_click,
+            outline_only: false,
+        };
         for (k,v) in hist {
-            map_hist.insert(k.clone(), transform_scale(*v) / transformed_max_cnt);
+            shading.counts.insert(k.clone(), *v);
+            shading.scaled.insert(k.clone(), transform_scale(*v) / transformed_max_cnt);
         }
 
         //Get all polygons
         let mut outpoly: Vec<Html> = Vec::new();
-        Self::process_geojson(&geojson, &mut outpoly, &mut geostat, &"".to_string(), &map_hist);
+        Self::process_geojson(&geojson, &mut outpoly, &mut geostat, &"".to_string(), &"".to_string(), &shading);
 
         //log::debug!("geostats {:?}",geostat);
 
+        //Legend below the map, in the same SVG so that it is included when the map is saved
+        let map_w = geostat.maxx - geostat.minx;
+        let legend_w = map_w * 0.5;
+        let legend_h = legend_w * LEGEND_H / LEGEND_W;
+        let legend_y = geostat.maxy + map_w * 0.02;
+
+        html! {
+            <div>
+                <svg viewBox={format!("{} {} {} {}", geostat.minx, geostat.miny, map_w, legend_y + legend_h - geostat.miny)}>
+                    { outpoly }
+                    { Self::draw_legend(max_cnt, geostat.minx, legend_y, legend_w) }
+                </svg>
+            </div>
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Country borders only, to draw other layers on. Returns polygons and the extent of the map
+    pub fn draw_outlines(geojson: &GeoJson) -> (Html, GeoStats) {
+        let mut geostat = GeoStats {
+            minx:0 as f64,
+            miny:0 as f64,
+            maxx:0 as f64,
+            maxy:0 as f64
+        };
+        let shading = MapShading {
+            counts: HashMap::new(),
+            scaled: HashMap::new(),
+            on_click: None,
+            outline_only: true,
+        };
+        let mut outpoly: Vec<Html> = Vec::new();
+        Self::process_geojson(&geojson, &mut outpoly, &mut geostat, &"".to_string(), &"".to_string(), &shading);
+        (outpoly.into_iter().collect::<Html>(), geostat)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Color scale with ticks at powers of 10, placed at x,y with a given width in the units of the map
+    fn draw_legend(max_cnt: i32, x: f64, y: f64, w: f64) -> Html {
+        let width = LEGEND_W - 20.0;
+        let transformed_max_cnt = transform_scale(max_cnt);
+
+        let mut ticks = vec![0];
+        let mut v = 1;
+        while v <= max_cnt {
+            ticks.push(v);
+            v *= 10;
+        }
+
+        let html_ticks = ticks.iter().map(|v| {
+            let x = 10.0 + width * transform_scale(*v) / transformed_max_cnt;
+            html! {
+                <g>
+                    <line x1={format!("{}", x)} x2={format!("{}", x)} y1="0" y2="14" stroke="black" stroke-width="0.5"/>
+                    <text x={format!("{}", x)} y="24" text-anchor="middle" style="font-size:9px;font-family:sans-serif;">{format!("{}", v)}</text>
+                </g>
+            }
+        }).collect::<Html>();
+
         html! {
-            <svg viewBox={format!("{} {} {} {}", geostat.minx, geostat.miny,  geostat.maxx-geostat.minx, geostat.maxy-geostat.miny)}>
-                { outpoly }
+            <svg x={format!("{}", x)} y={format!("{}", y)} width={format!("{}", w)} height={format!("{}", w * LEGEND_H / LEGEND_W)}
+                viewBox={format!("0 0 {} {}", LEGEND_W, LEGEND_H)}>
+                <defs>
+                    <linearGradient id="mapscale">
+                        <stop offset="0%" stop-color={shade_color(0.0)}/>
+                        <stop offset="100%" stop-color={shade_color(1.0)}/>
+                    </linearGradient>
+                </defs>
+                <rect x="10" y="0" width={format!("{}", width)} height="10" fill="url(#mapscale)"/>
+                { html_ticks }
             </svg>
         }
     }
@@ -78,13 +180,14 @@ impl GeoMapView {
 
     ////////////////////////////////////////////////////////////
     /// Process top-level GeoJSON Object
-    fn process_geojson(gj: &GeoJson, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, hist: &HashMap<String,f64>) {
+    fn process_geojson(gj: &GeoJson, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, current_name: &String, shading: &MapShading) {
         match *gj {
             GeoJson::FeatureCollection(ref ctn) => {
 
                 for feature in &ctn.features {
 
                     let mut current_country = current_country;
+                    let mut current_name = current_name;
                     if let Some(map) = &feature.properties {
                         let iso_a3=map.get("iso_a3");
                         if let Some(f) = iso_a3 {
@@ -92,9 +195,12 @@ impl GeoMapView {
                                 current_country = s;
                             }
                         }
+                        if let Some(serde_json::Value::String(s)) = map.get("name") {
+                            current_name = s;
+                        }
                     }
                     if let Some(ref geom) = feature.geometry {
-                        Self::process_geometry(geom, outpoly, geostats, current_country, hist)
+                        Self::process_geometry(geom, outpoly, geostats, current_country, current_name, shading)
                     }
                 }
             }
@@ -109,10 +215,10 @@ impl GeoMapView {
                 }
 
                 if let Some(ref geom) = feature.geometry {
-                    Self::process_geometry(geom, outpoly, geostats, current_country, hist)
+                    Self::process_geometry(geom, outpoly, geostats, current_country, current_name, shading)
                 }
             }
-            GeoJson::Geometry(ref geometry) => Self::process_geometry(geometry, outpoly, geostats, current_country, hist),
+            GeoJson::Geometry(ref geometry) => Self::process_geometry(geometry, outpoly, geostats, current_country, current_name, shading),
         }
     }
 
@@ -120,23 +226,23 @@ impl GeoMapView {
     ////////////////////////////////////////////////////////////
     /// Process GeoJSON geometries
     /// https://datatracker.ietf.org/doc/html/rfc7946#section-3.1.6
-    fn process_geometry(geom: &Geometry, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, hist: &HashMap<String,f64>) {
+    fn process_geometry(geom: &Geometry, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, current_name: &String, shading: &MapShading) {
         match &geom.value {
             Value::Polygon(polytype) => {
                 for p in polytype {
-                    Self::process_poly(p, outpoly, geostats, current_country, hist);
+                    Self::process_poly(p, outpoly, geostats, current_country, current_name, shading);
                 }
             },
             Value::MultiPolygon(polytype) => {
                 for pp in polytype {
                     for p in pp {
-                        Self::process_poly(p, outpoly, geostats, current_country, hist);
+                        Self::process_poly(p, outpoly, geostats, current_country, current_name, shading);
                     }
                 }
             },
             Value::GeometryCollection(ref gc) => {
                 for geometry in gc {
-                    Self::process_geometry(geometry, outpoly, geostats, current_country, hist)
+                    Self::process_geometry(geometry, outpoly, geostats, current_country, current_name, shading)
                 }
             }
             // Point, LineString, and their Multi– counterparts
@@ -147,13 +253,10 @@ impl GeoMapView {
 
     ////////////////////////////////////////////////////////////
     /// x
-    fn process_poly(points: &Vec<Vec<f64>>, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, hist: &HashMap<String,f64>) {
+    fn process_poly(points: &Vec<Vec<f64>>, outpoly: &mut Vec<Html>, geostats: &mut GeoStats, current_country: &String, current_name: &String, shading: &MapShading) {
 
-        let color = if let Some(cnt) = hist.get(current_country) {
-            let cnt = (*cnt * 255_f64) as u8;
-            let color = Color {red: cnt, blue: 0, green: 0};
-            //log::debug!("{:?}", color);
-            color.as_string()
+        let color = if let Some(cnt) = shading.scaled.get(current_country) {
+            shade_color(*cnt)
         } else {
             "darkgray".to_string()
         };
@@ -180,11 +283,30 @@ impl GeoMapView {
             }
             //log::debug!("c {:?}",current_country);
         }
+
+        if shading.outline_only {
+            outpoly.push(html! {
+                <polygon points={outs} fill="#eeeeee" stroke="gray" stroke-width="0.5" vector-effect="non-scaling-stroke"/>
+            });
+            return;
+        }
+
+        //Tooltip and click handler
+        let cnt = shading.counts.get(current_country).cloned().unwrap_or(0);
+        let tooltip = format!("{}: {}", current_name, cnt);
+        let onclick = shading.on_click.clone().map(|cb| {
+            let code = current_country.clone();
+            Callback::from(move |_e: MouseEvent| cb.emit(code.clone()))
+        });
+        let class = if onclick.is_some() { "mapcountry clickable" } else { "mapcountry" };
+
         outpoly.push(html! {
-            <polygon points={outs} fill={color} stroke="black" /> 
+            <polygon points={outs} fill={color} stroke="black" class={class} onclick={onclick}>
+                <title>{tooltip}</title>
+            </polygon>
         });
     }
 
 
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use geojson::GeoJson;
+use my_web_app::PointMap;
+use web_sys::Element;
+use yew::{html, Component, Context, Html, MouseEvent, NodeRef, WheelEvent};
+use yew::Properties;
+
+use crate::component_map::GeoMapView;
+use crate::model_stats::category_color;
+
+
+/// Number of clusters across the width of the view
+const CLUSTER_CELLS: f64 = 50.0;
+
+/// Number of values of the color column given their own color; the rest are "Other"
+const MAX_COLORS: usize = 9;
+
+/// Color of values not given their own color
+const OTHER_COLOR: &str = "#444444";
+
+
+////////////////////////////////////////////////////////////
+/// Message sent to the event system for updating the page
+#[derive(Debug)]
+pub enum MsgPointMap {
+    MouseDown(f64,f64),
+    MouseMove(f64,f64, bool),
+    MouseWheel(f64,f64, f64),
+    ZoomTo(f64,f64),
+    ResetView,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Properties for PointMapView
+#[derive(Properties)]
+pub struct Props {
+    pub geojson: Rc<GeoJson>,
+    pub pointmap: Arc<PointMap>,
+}
+impl PartialEq for Props {
+    fn eq(&self, other: &Self) -> bool {
+        //Compare by position in memory, as for AsyncData; avoids comparing the map polygon by polygon
+        Rc::ptr_eq(&self.geojson, &other.geojson) && Arc::ptr_eq(&self.pointmap, &other.pointmap)
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Strains close enough to be drawn as one circle
+struct Cluster {
+    x: f64,
+    y: f64,
+    ids: Vec<String>,
+    values: HashMap<String, usize>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Isolate locations drawn on top of the country borders, clustered depending on the zoom.
+/// Drag to pan, wheel to zoom, click a cluster to zoom in on it
+pub struct PointMapView {
+    node_ref: NodeRef,
+    last_pos: (f64,f64),
+    dragged: bool,
+    background: Html,
+    full_extent: (f64,f64,f64,f64),
+    /// Center and width of the view, in map coordinates (x=longitude, y=-latitude)
+    view_x: f64,
+    view_y: f64,
+    view_w: f64,
+    /// Colors of the most common values of the color column
+    colors: HashMap<String, &'static str>,
+    legend: Vec<(String, &'static str)>,
+}
+
+impl Component for PointMapView {
+    type Message = MsgPointMap;
+    type Properties = Props;
+
+    ////////////////////////////////////////////////////////////
+    /// Create this component
+    fn create(ctx: &Context<Self>) -> Self {
+        let (background, geostat) = GeoMapView::draw_outlines(&ctx.props().geojson);
+        let mut view = Self {
+            node_ref: NodeRef::default(),
+            last_pos: (0.0,0.0),
+            dragged: false,
+            background,
+            full_extent: (geostat.minx, geostat.miny, geostat.maxx, geostat.maxy),
+            view_x: 0.0,
+            view_y: 0.0,
+            view_w: 0.0,
+            colors: HashMap::new(),
+            legend: Vec::new(),
+        };
+        view.reset_view();
+        view.update_colors(ctx);
+        view
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Properties changed; recompute colors
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.update_colors(ctx);
+        true
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Handle an update message
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse button pressed
+            MsgPointMap::MouseDown(x,y) => {
+                self.last_pos = (x,y);
+                self.dragged = false;
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse has moved. Pan if the button is held
+            MsgPointMap::MouseMove(x,y, press_left) => {
+                let last_pos = self.last_pos;
+                self.last_pos = (x,y);
+                if press_left {
+                    self.dragged = true;
+                    let scale = self.view_w / self.element_width();
+                    self.view_x -= (x - last_pos.0) * scale;
+                    self.view_y -= (y - last_pos.1) * scale;
+                    return true;
+                }
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse wheel rotated. Zoom around the cursor
+            MsgPointMap::MouseWheel(x,y, dy) => {
+                let (wx, wy) = self.screen2map(x,y);
+                let scale = (10.0f64).powf(dy / 500.0);
+                let new_w = (self.view_w * scale).clamp(0.05, self.full_width());
+                let f = new_w / self.view_w;
+                self.view_x = wx + (self.view_x - wx) * f;
+                self.view_y = wy + (self.view_y - wy) * f;
+                self.view_w = new_w;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Center on a cluster and zoom in, unless the click ended panning
+            MsgPointMap::ZoomTo(x,y) => {
+                if self.dragged {
+                    return false;
+                }
+                self.view_x = x;
+                self.view_y = y;
+                self.view_w = (self.view_w / 4.0).max(0.05);
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Show the whole world
+            MsgPointMap::ResetView => {
+                self.reset_view();
+                true
+            },
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Render this component
+    fn view(&self, ctx: &Context<Self>) -> Html {
+
+        let cb_mousedown = ctx.link().callback(move |e: MouseEvent | {
+            MsgPointMap::MouseDown(e.client_x() as f64, e.client_y() as f64)
+        });
+
+        let cb_mousemoved = ctx.link().callback(move |e: MouseEvent | {
+            e.prevent_default();
+            let press_left = e.buttons() & 1 > 0;
+            MsgPointMap::MouseMove(e.client_x() as f64, e.client_y() as f64, press_left)
+        });
+
+        let cb_mousewheel = ctx.link().callback(move |e: WheelEvent | {
+            e.prevent_default();
+            MsgPointMap::MouseWheel(e.client_x() as f64, e.client_y() as f64, e.delta_y())
+        });
+
+        //Circles get larger with the number of strains, but keep the same size on screen while zooming
+        let unit = self.view_w / 500.0;
+        let html_clusters = self.make_clusters(ctx).into_iter().map(|cluster| {
+            let n = cluster.ids.len();
+            let mut values: Vec<(&String, &usize)> = cluster.values.iter().collect();
+            values.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let color = values.first().map(|(v,_)| self.color_of(v)).unwrap_or(OTHER_COLOR);
+
+            let tooltip = if n == 1 {
+                let value = values.first().map(|(v,_)| v.as_str()).unwrap_or("");
+                format!("{}\n{}", cluster.ids[0], value)
+            } else {
+                let mut tooltip = format!("{} strains", n);
+                for (v, cnt) in values.iter().take(5) {
+                    tooltip.push_str(&format!("\n{}: {}", v, cnt));
+                }
+                tooltip
+            };
+
+            let (x, y) = (cluster.x, cluster.y);
+            let onclick = ctx.link().callback(move |_e: MouseEvent| MsgPointMap::ZoomTo(x, y));
+            html! {
+                <circle
+                    cx={format!("{}", x)} cy={format!("{}", y)}
+                    r={format!("{}", unit * (2.0 + 1.5 * (n as f64).sqrt()).min(15.0))}
+                    fill={color} fill-opacity="0.8" stroke="white" stroke-width="0.5" vector-effect="non-scaling-stroke"
+                    style="cursor:pointer;"
+                    onclick={onclick}>
+                    <title>{tooltip}</title>
+                </circle>
+            }
+        }).collect::<Html>();
+
+        let html_legend = if self.legend.len() > 1 {
+            html! {
+                <div class="commontext">
+                {
+                    self.legend.iter().map(|(name, color)| html! {
+                        <span style="margin-right:10px;">
+                            <span style={format!("display:inline-block;width:10px;height:10px;border-radius:50%;background:{};", color)}></span>
+                            {format!(" {}", name)}
+                        </span>
+                    }).collect::<Html>()
+                }
+                </div>
+            }
+        } else {
+            html! {""}
+        };
+
+        let view_h = self.view_w / 2.0;
+        html! {
+            <div>
+                <svg
+                    ref={self.node_ref.clone()}
+                    viewBox={format!("{} {} {} {}", self.view_x - self.view_w / 2.0, self.view_y - view_h / 2.0, self.view_w, view_h)}
+                    style="width:100%; border:1px solid #000000; background:#f8fbff;"
+                    onmousedown={cb_mousedown}
+                    onmousemove={cb_mousemoved}
+                    onwheel={cb_mousewheel}>
+                    { self.background.clone() }
+                    { html_clusters }
+                </svg>
+                <div class="commontext">
+                    {format!("{} strains with a location. Drag to pan, scroll to zoom, click a circle to zoom in on it ", ctx.props().pointmap.points.len())}
+                    <button onclick={ctx.link().callback(|_| MsgPointMap::ResetView)}>{"Reset view"}</button>
+                </div>
+                { html_legend }
+            </div>
+        }
+    }
+}
+
+impl PointMapView {
+
+    ////////////////////////////////////////////////////////////
+    /// Width of the whole map
+    fn full_width(&self) -> f64 {
+        self.full_extent.2 - self.full_extent.0
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Show the whole map
+    fn reset_view(&mut self) {
+        let (minx, miny, maxx, maxy) = self.full_extent;
+        self.view_x = (minx + maxx) / 2.0;
+        self.view_y = (miny + maxy) / 2.0;
+        self.view_w = (maxx - minx).max(2.0 * (maxy - miny));
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Width of the map on the screen, in pixels
+    fn element_width(&self) -> f64 {
+        self.node_ref.cast::<Element>()
+            .map(|e| e.get_bounding_client_rect().width())
+            .filter(|w| *w > 0.0)
+            .unwrap_or(1.0)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Convert a position on the screen to map coordinates
+    fn screen2map(&self, x: f64, y: f64) -> (f64, f64) {
+        if let Some(e) = self.node_ref.cast::<Element>() {
+            let rect = e.get_bounding_client_rect();
+            if rect.width() > 0.0 {
+                let scale = self.view_w / rect.width();
+                let view_h = self.view_w / 2.0;
+                return (
+                    self.view_x - self.view_w / 2.0 + (x - rect.left()) * scale,
+                    self.view_y - view_h / 2.0 + (y - rect.top()) * scale,
+                );
+            }
+        }
+        (self.view_x, self.view_y)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Give the most common values of the color column their own colors
+    fn update_colors(&mut self, ctx: &Context<Self>) {
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for p in &ctx.props().pointmap.points {
+            *counts.entry(&p.value).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(&String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        self.colors.clear();
+        self.legend.clear();
+        for (i, (value, _)) in counts.iter().take(MAX_COLORS).enumerate() {
+            let color = category_color(i);
+            self.colors.insert((*value).clone(), color);
+            self.legend.push(((*value).clone(), color));
+        }
+        if counts.len() > MAX_COLORS {
+            self.legend.push(("Other".to_string(), OTHER_COLOR));
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Color of a value of the color column
+    fn color_of(&self, value: &String) -> &'static str {
+        self.colors.get(value).cloned().unwrap_or(OTHER_COLOR)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Group points on a grid, with cells getting smaller as one zooms in
+    fn make_clusters(&self, ctx: &Context<Self>) -> Vec<Cluster> {
+        let cell = self.view_w / CLUSTER_CELLS;
+        let mut map_cluster: HashMap<(i64,i64), Cluster> = HashMap::new();
+        for p in &ctx.props().pointmap.points {
+            let x = p.lon as f64;
+            let y = -p.lat as f64;
+            let key = ((x / cell).floor() as i64, (y / cell).floor() as i64);
+            let cluster = map_cluster.entry(key).or_insert_with(|| Cluster {
+                x: 0.0,
+                y: 0.0,
+                ids: Vec::new(),
+                values: HashMap::new(),
+            });
+            cluster.x += x;
+            cluster.y += y;
+            cluster.ids.push(p.id.clone());
+            *cluster.values.entry(p.value.clone()).or_insert(0) += 1;
+        }
+
+        //Place clusters at the mean of their points. Larger clusters are drawn first, below smaller ones
+        let mut list_cluster: Vec<Cluster> = map_cluster.into_values().map(|mut c| {
+            let n = c.ids.len() as f64;
+            c.x /= n;
+            c.y /= n;
+            c
+        }).collect();
+        list_cluster.sort_by(|a, b| b.ids.len().cmp(&a.ids.len()));
+        list_cluster
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::{HashMap, HashSet};
+
+use my_web_app::TableData;
+use wasm_bindgen::JsCast;
+use web_sys::window;
+use web_sys::{DomRect, EventTarget, HtmlCanvasElement, WebGlBuffer, WebGlProgram, WebGlRenderingContext as GL};
+use yew::{html, Callback, Component, Context, Html, MouseEvent, NodeRef, WheelEvent};
+use yew::Properties;
+
+use crate::appstate::AsyncData;
+use crate::core_model::MsgCore;
+use crate::model_stats::category_color;
+use crate::treeview::{Camera2D, Rectangle2D};
+use crate::treeview::treeview_main::parse_rgb_f64;
+
+
+/// Height of the canvas, in pixels
+const CANVAS_H: usize = 400;
+
+/// Largest distance to a point, in camera coordinates, for a click to hit it
+const CLICK_RADIUS: f32 = 0.03;
+
+
+////////////////////////////////////////////////////////////
+/// Message sent to the event system for updating the page
+#[derive(Debug)]
+pub enum MsgScatter {
+    MouseDown(f32,f32, bool),
+    MouseMove(f32,f32, bool),
+    MouseUp(f32,f32),
+    MouseWheel(f32),
+}
+
+
+////////////////////////////////////////////////////////////
+/// Properties for ScatterView
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub on_propagate: Callback<MsgCore>,
+    pub tabledata: AsyncData<TableData>,
+    pub selected_strains: HashSet<String>,
+    /// Changes whenever selected_strains does
+    pub selection_version: u64,
+    pub col_x: String,
+    pub col_y: String,
+    pub col_color: String,
+}
+
+
+////////////////////////////////////////////////////////////
+/// One point to draw. y is negated so that larger values are drawn higher up
+#[derive(Debug)]
+struct ScatterPoint {
+    x: f32,
+    y: f32,
+    id: String,
+    category: usize,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Scatter plot of two numeric columns of the strain table, drawn with WebGL.
+/// Drag to pan, wheel to zoom, click to toggle a strain, shift-drag to lasso strains
+pub struct ScatterView {
+    node_ref: NodeRef,
+    last_pos: (f32,f32),
+    down_pos: Option<(f32,f32)>,
+    camera: Camera2D,
+    points: Vec<ScatterPoint>,
+    categories: Vec<String>,
+    lasso: Option<Vec<(f32,f32)>>,
+    last_fit: Option<(AsyncData<TableData>, String, String)>,
+    gl: Option<ScatterGl>,
+}
+
+impl Component for ScatterView {
+    type Message = MsgScatter;
+    type Properties = Props;
+
+    ////////////////////////////////////////////////////////////
+    /// Create this component
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut view = Self {
+            node_ref: NodeRef::default(),
+            last_pos: (0.0,0.0),
+            down_pos: None,
+            camera: Camera2D::new(),
+            points: Vec::new(),
+            categories: Vec::new(),
+            lasso: None,
+            last_fit: None,
+            gl: None,
+        };
+        view.update_points(ctx);
+        view
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Properties changed; recompute points
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.update_points(ctx);
+        true
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Handle an update message
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse button pressed. With shift, a lasso is started
+            MsgScatter::MouseDown(x,y, shift) => {
+                self.last_pos = (x,y);
+                self.down_pos = Some((x,y));
+                if shift {
+                    self.lasso = Some(vec![self.camera.cam2world(x,y)]);
+                }
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse has moved
+            MsgScatter::MouseMove(x,y, press_left) => {
+                let last_pos = self.last_pos;
+                self.last_pos = (x,y);
+
+                if let Some(lasso) = &mut self.lasso {
+                    lasso.push(self.camera.cam2world(x,y));
+                    return true;
+                }
+
+                //Handle panning
+                if press_left {
+                    let dx = x - last_pos.0;
+                    let dy = y - last_pos.1;
+                    self.camera.x -= dx / self.camera.zoom_x;
+                    self.camera.y -= dy / self.camera.zoom_y;
+                    return true;
+                }
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse button released. Either ends a lasso, or is a click if the mouse did not move
+            MsgScatter::MouseUp(x,y) => {
+                let down_pos = self.down_pos.take();
+
+                if let Some(lasso) = self.lasso.take() {
+                    let list_sel: Vec<String> = self.points.iter()
+                        .filter(|p| point_in_polygon((p.x, p.y), &lasso))
+                        .map(|p| p.id.clone())
+                        .collect();
+                    if !list_sel.is_empty() {
+                        ctx.props().on_propagate.emit(MsgCore::SetStrainsSelected(list_sel, true));
+                    }
+                    return true;
+                }
+
+                if let Some((dx,dy)) = down_pos {
+                    if (x-dx).abs() < 0.01 && (y-dy).abs() < 0.01 {
+                        if let Some(id) = self.closest_point(x,y) {
+                            let is_selected = ctx.props().selected_strains.contains(&id);
+                            ctx.props().on_propagate.emit(MsgCore::SetStrainsSelected(vec![id], !is_selected));
+                        }
+                    }
+                }
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse wheel rotated
+            MsgScatter::MouseWheel(dy) => {
+                let (cx,cy) = self.last_pos;
+                let (wx, wy) = self.camera.cam2world(cx, cy);
+                let scale = (10.0f32).powf(dy / 100.0);
+                self.camera.zoom_around(wx,wy, scale);
+                true
+            },
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Render this component
+    fn view(&self, ctx: &Context<Self>) -> Html {
+
+        let cb_mousedown = ctx.link().callback(move |e: MouseEvent | {
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            MsgScatter::MouseDown(x_cam, y_cam, e.shift_key())
+        });
+
+        let cb_mousemoved = ctx.link().callback(move |e: MouseEvent | {
+            e.prevent_default();
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            let press_left = e.buttons() & 1 > 0;
+            MsgScatter::MouseMove(x_cam, y_cam, press_left)
+        });
+
+        let cb_mouseup = ctx.link().callback(move |e: MouseEvent | {
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            MsgScatter::MouseUp(x_cam, y_cam)
+        });
+
+        let cb_mousewheel = ctx.link().callback(move |e: WheelEvent | {
+            e.prevent_default();
+            MsgScatter::MouseWheel(e.delta_y() as f32)
+        });
+
+        let (canvas_w, canvas_h) = canvas_size();
+
+        //Lasso being drawn, as an overlay
+        let html_lasso = if let Some(lasso) = &self.lasso {
+            let points = lasso.iter().map(|(wx,wy)| {
+                let (cx,cy) = self.camera.world2cam(*wx, *wy);
+                format!("{},{}", (cx+1.0)/2.0*canvas_w as f32, (cy+1.0)/2.0*canvas_h as f32)
+            }).collect::<Vec<_>>().join(" ");
+            html! {
+                <svg width={format!("{}", canvas_w)} height={format!("{}", canvas_h)} style="position:absolute;left:0;top:0;pointer-events:none;">
+                    <polygon points={points} style="fill:rgba(205,16,118,0.1);stroke:#cd1076;stroke-width:1;"/>
+                </svg>
+            }
+        } else {
+            html! {""}
+        };
+
+        //Legend of the color column
+        let html_legend = if self.categories.len() > 1 {
+            html! {
+                <div class="commontext">
+                {
+                    self.categories.iter().enumerate().map(|(i, name)| html! {
+                        <span style="margin-right:10px;">
+                            <span style={format!("display:inline-block;width:10px;height:10px;background:{};", category_color(i))}></span>
+                            {format!(" {}", name)}
+                        </span>
+                    }).collect::<Html>()
+                }
+                </div>
+            }
+        } else {
+            html! {""}
+        };
+
+        html! {
+            <div>
+                <div style={format!("position: relative; height: {}px;", canvas_h)}>
+                    <canvas
+                        ref={self.node_ref.clone()}
+                        style="border:1px solid #000000;"
+                        onmousedown={cb_mousedown}
+                        onmousemove={cb_mousemoved}
+                        onmouseup={cb_mouseup}
+                        onwheel={cb_mousewheel}
+                        width={format!{"{}", canvas_w}}
+                        height={format!{"{}", canvas_h}}
+                    />
+                    { html_lasso }
+                </div>
+                <div class="commontext">
+                    {format!("{} points. Drag to pan, scroll to zoom, click a point to toggle it, shift-drag to select an area", self.points.len())}
+                </div>
+                { html_legend }
+            </div>
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Called after DOM has been created
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+
+        //Fit camera whenever new data or columns are shown
+        let fit_key = (ctx.props().tabledata.clone(), ctx.props().col_x.clone(), ctx.props().col_y.clone());
+        if self.last_fit.as_ref() != Some(&fit_key) {
+            self.camera.fit_reduction(&self.get_bounding_rect());
+            self.last_fit = Some(fit_key);
+        }
+
+        let canvas = if let Some(canvas) = self.node_ref.cast::<HtmlCanvasElement>() {
+            canvas
+        } else {
+            return;
+        };
+
+        //Set up program once per canvas, and vertex data only when the points or their colors change
+        let same_canvas = self.gl.as_ref().map(|g| g.canvas == canvas).unwrap_or(false);
+        if !same_canvas {
+            self.gl = Some(ScatterGl::new(canvas));
+        }
+        let scatter_gl = self.gl.as_mut().unwrap();
+        let props = ctx.props();
+        let vertex_key = (props.tabledata.clone(), props.col_x.clone(), props.col_y.clone(), props.col_color.clone(), props.selection_version);
+        if scatter_gl.uploaded_for.as_ref() != Some(&vertex_key) {
+            scatter_gl.upload_points(&self.points, self.categories.len(), &props.selected_strains);
+            scatter_gl.uploaded_for = Some(vertex_key);
+        }
+
+        scatter_gl.draw(&self.camera);
+    }
+}
+
+impl ScatterView {
+
+    ////////////////////////////////////////////////////////////
+    /// Collect points from the strain table. Rows without numeric values are left out
+    fn update_points(&mut self, ctx: &Context<Self>) {
+        self.points.clear();
+        self.categories.clear();
+
+        let props = ctx.props();
+        if let AsyncData::Loaded(tabledata) = &props.tabledata {
+            let col_index = |name: &String| tabledata.columns.iter().position(|c| c == name);
+            let (i_x, i_y) = match (col_index(&props.col_x), col_index(&props.col_y)) {
+                (Some(i_x), Some(i_y)) => (i_x, i_y),
+                _ => return,
+            };
+            let i_color = col_index(&props.col_color);
+
+            let mut map_category: HashMap<String, usize> = HashMap::new();
+            for row in &tabledata.rows {
+                let x = row.get(i_x).and_then(|v| v.parse::<f32>().ok());
+                let y = row.get(i_y).and_then(|v| v.parse::<f32>().ok());
+                if let (Some(x), Some(y)) = (x, y) {
+                    let category = match i_color.and_then(|i| row.get(i)) {
+                        Some(value) => {
+                            let next = map_category.len();
+                            *map_category.entry(value.clone()).or_insert_with(|| {
+                                self.categories.push(value.clone());
+                                next
+                            })
+                        },
+                        None => 0,
+                    };
+                    self.points.push(ScatterPoint {
+                        x,
+                        y: -y,
+                        id: row.get(0).cloned().unwrap_or_default(),
+                        category,
+                    });
+                }
+            }
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Extent of all points
+    fn get_bounding_rect(&self) -> Rectangle2D {
+        let mut rect = Rectangle2D { x1: f32::MAX, x2: f32::MIN, y1: f32::MAX, y2: f32::MIN };
+        for p in &self.points {
+            rect.x1 = rect.x1.min(p.x);
+            rect.x2 = rect.x2.max(p.x);
+            rect.y1 = rect.y1.min(p.y);
+            rect.y2 = rect.y2.max(p.y);
+        }
+        if self.points.is_empty() {
+            rect = Rectangle2D { x1: 0.0, x2: 1.0, y1: 0.0, y2: 1.0 };
+        }
+        //Avoid zero extent if all values are the same
+        if rect.x2 - rect.x1 <= 0.0 {
+            rect.x1 -= 0.5;
+            rect.x2 += 0.5;
+        }
+        if rect.y2 - rect.y1 <= 0.0 {
+            rect.y1 -= 0.5;
+            rect.y2 += 0.5;
+        }
+        rect
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Strain closest to a position given in camera coordinates, if close enough
+    fn closest_point(&self, cx: f32, cy: f32) -> Option<String> {
+        let mut best: Option<(f32, &ScatterPoint)> = None;
+        for p in &self.points {
+            let (px, py) = self.camera.world2cam(p.x, p.y);
+            let d = (px-cx).powi(2) + (py-cy).powi(2);
+            if best.map(|(bd, _)| d < bd).unwrap_or(true) {
+                best = Some((d, p));
+            }
+        }
+        best.filter(|(d, _)| d.sqrt() < CLICK_RADIUS).map(|(_, p)| p.id.clone())
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// WebGL state kept between renders: the program and the buffer of points
+struct ScatterGl {
+    canvas: HtmlCanvasElement,
+    gl: GL,
+    program: WebGlProgram,
+    vertex_buffer: WebGlBuffer,
+    num_points: i32,
+    /// Table, columns and selection the vertex buffer was computed for
+    uploaded_for: Option<(AsyncData<TableData>, String, String, String, u64)>,
+    /// Camera and canvas size the uniforms were set for
+    camera_for: Option<(Camera2D, u32, u32)>,
+}
+impl ScatterGl {
+
+    ////////////////////////////////////////////////////////////
+    /// Compile shaders and create the buffer. Same shaders as the tree
+    fn new(canvas: HtmlCanvasElement) -> ScatterGl {
+        let gl: GL = canvas
+            .get_context("webgl")
+            .unwrap()
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+
+        let vert_shader = gl.create_shader(GL::VERTEX_SHADER).unwrap();
+        gl.shader_source(&vert_shader, include_str!("./treeview/umap.vert"));
+        gl.compile_shader(&vert_shader);
+        let frag_shader = gl.create_shader(GL::FRAGMENT_SHADER).unwrap();
+        gl.shader_source(&frag_shader, include_str!("./treeview/umap.frag"));
+        gl.compile_shader(&frag_shader);
+
+        let program = gl.create_program().unwrap();
+        gl.attach_shader(&program, &vert_shader);
+        gl.attach_shader(&program, &frag_shader);
+        gl.link_program(&program);
+        gl.use_program(Some(&program));
+        gl.uniform1f(gl.get_uniform_location(&program, "u_point_size").as_ref(), 4.0);
+
+        //Position and color are interleaved in one buffer
+        let vertex_buffer = gl.create_buffer().unwrap();
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vertex_buffer));
+        let sizeof_float = 4;
+        let a_position = gl.get_attrib_location(&program, "a_position") as u32;
+        gl.enable_vertex_attrib_array(a_position);
+        gl.vertex_attrib_pointer_with_i32(a_position, 3, GL::FLOAT, false, sizeof_float*6, 0);
+        let a_color = gl.get_attrib_location(&program, "a_color") as u32;
+        gl.enable_vertex_attrib_array(a_color);
+        gl.vertex_attrib_pointer_with_i32(a_color, 3, GL::FLOAT, false, sizeof_float*6, sizeof_float*3);
+
+        ScatterGl {
+            canvas,
+            gl,
+            program,
+            vertex_buffer,
+            num_points: 0,
+            uploaded_for: None,
+            camera_for: None,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Upload position and color of each point. Selected points are drawn last, on top of the others
+    fn upload_points(&mut self, points: &[ScatterPoint], num_categories: usize, selected: &HashSet<String>) {
+        let palette: Vec<(f32,f32,f32)> = (0..num_categories.max(1))
+            .map(|i| parse_rgb_f64(&category_color(i).to_string()))
+            .collect();
+        let mut vec_vertex: Vec<f32> = Vec::with_capacity(points.len() * 6);
+        for draw_selected in [false, true].iter() {
+            for p in points {
+                if selected.contains(&p.id) != *draw_selected {
+                    continue;
+                }
+                let thecol = if *draw_selected {
+                    (0.8, 0.06, 0.46)  // #cd1076
+                } else {
+                    palette[p.category % palette.len()]
+                };
+                vec_vertex.push(p.x);
+                vec_vertex.push(p.y);
+                vec_vertex.push(0.0);
+                vec_vertex.push(thecol.0);
+                vec_vertex.push(thecol.1);
+                vec_vertex.push(thecol.2);
+            }
+        }
+
+        let js_vertex = js_sys::Float32Array::from(vec_vertex.as_slice());
+        self.gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.vertex_buffer));
+        self.gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_vertex, GL::STATIC_DRAW);
+        self.num_points = points.len() as i32;
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Draw the points as seen by the camera. Uniforms are only set after panning, zooming or resizing
+    fn draw(&mut self, camera: &Camera2D) {
+        let gl = &self.gl;
+        let program = &self.program;
+        gl.use_program(Some(program));
+
+        let camera_key = (camera.clone(), self.canvas.width(), self.canvas.height());
+        if self.camera_for.as_ref() != Some(&camera_key) {
+            gl.uniform1f(gl.get_uniform_location(program, "u_camera_x").as_ref(), camera.x);
+            gl.uniform1f(gl.get_uniform_location(program, "u_camera_y").as_ref(), camera.y);
+            gl.uniform1f(gl.get_uniform_location(program, "u_camera_zoom_x").as_ref(), camera.zoom_x);
+            gl.uniform1f(gl.get_uniform_location(program, "u_camera_zoom_y").as_ref(), camera.zoom_y);
+            gl.uniform1f(gl.get_uniform_location(program, "u_display_w").as_ref(), self.canvas.width() as f32);
+            gl.uniform1f(gl.get_uniform_location(program, "u_display_h").as_ref(), self.canvas.height() as f32);
+            self.camera_for = Some(camera_key);
+        }
+
+        gl.clear_color(1.0, 1.0, 1.0, 1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+        gl.draw_arrays(GL::POINTS, 0, self.num_points);
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Is the point inside the polygon? Even-odd rule
+pub fn point_in_polygon(p: (f32,f32), polygon: &[(f32,f32)]) -> bool {
+    let mut inside = false;
+    if polygon.len() < 3 {
+        return false;
+    }
+    //Each edge goes from the previous corner to this one
+    let mut prev = polygon[polygon.len() - 1];
+    for &(xi, yi) in polygon {
+        let (xj, yj) = prev;
+        if (yi > p.1) != (yj > p.1) && p.0 < (xj - xi) * (p.1 - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        prev = (xi, yi);
+    }
+    inside
+}
+
+
+////////////////////////////////////////////////////////////
+/// Size of the canvas in pixels. Not automatic via CSS
+fn canvas_size() -> (usize, usize) {
+    let window = window().expect("no window");
+    let window_w = window.inner_width().expect("failed to get width").as_f64().unwrap();
+    ((window_w*0.95) as usize, CANVAS_H)
+}
+
+
+////////////////////////////////////////////////////////////
+/// Get current camera position from a mouse event
+fn mouseevent_get_cx(e: &MouseEvent) -> (f32,f32) {
+    let target: Option<EventTarget> = e.target();
+    let canvas: HtmlCanvasElement = target.and_then(|t| t.dyn_into::<HtmlCanvasElement>().ok()).expect("wrong type");
+
+    let rect:DomRect = canvas.get_bounding_client_rect();
+    let x = e.client_x() - (rect.left() as i32);
+    let y = e.client_y() - (rect.top() as i32);
+
+    let w = rect.width() as f32;
+    let h = rect.height() as f32;
+
+    ((x as f32 - w/2.0)/(w/2.0), (y as f32 - h/2.0)/(h/2.0))
+}
@@ -1,24 +1,40 @@
 use std::collections::HashSet;
 use std::io::Cursor;
+use std::rc::Rc;
 
+use my_web_app::ColumnValues;
+use my_web_app::ColumnValuesRequest;
 use my_web_app::ComparisonType;
+use my_web_app::CrossTab;
+use my_web_app::CrossTabRequest;
 use my_web_app::DatabaseMetadata;
+use my_web_app::DistanceMatrix;
+use my_web_app::PointMap;
+use my_web_app::PointMapRequest;
+use my_web_app::ReleaseList;
 use my_web_app::StrainRequest;
 use my_web_app::TableData;
 use my_web_app::SearchSettings;
 use my_web_app::SearchCriteria;
+use my_web_app::SearchStats;
+use my_web_app::SketchSearchResult;
 
 use geojson::GeoJson;
 
 
-use my_web_app::TreeData;
+use my_web_app::treelayout::TreeLayoutData;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::Uint8Array;
 use web_sys::window;
+use web_sys::File;
 use yew::prelude::*;
 
 use crate::appstate::AsyncData;
+use crate::download::download_text_file;
+use crate::model_crosstab::CrossTabMode;
 use crate::resize::ComponentSize;
 use crate::resize::ComponentSizeObserver;
-use crate::treeview::treelayout::TreeLayout;
+use crate::treeview::treelayout::{TreeLayout, TreeShape};
 
 ////////////////////////////////////////////////////////////
 /// Which page is currently being shown?
@@ -51,16 +67,32 @@ pub enum MsgCore {
 
     OpenPage(CurrentPage),
     StartQuery,
+    StartQueryByID(Vec<String>),
     SetQuery(AsyncData<TableData>),
     SetSearchControlVisibility(bool),
     AddSearchFilter,
     DeleteSearchFilter(usize),
+    SearchByValue(String, String),
 
     FetchDatabaseMetadata,
     SetDatabaseMetadata(DatabaseMetadata),
 
+    FetchReleases,
+    SetReleases(ReleaseList),
+    SetRelease(String),
+
     FetchTreeData,
     SetTreeData(TreeLayout),
+    FetchTreeMetadata,
+    SetTreeMetadata(AsyncData<ColumnValues>),
+    SetTreeLabelColumn(String),
+    SetTreeColorColumn(String),
+    SetTreeLayout(TreeShape, bool),
+    FindInTree(String),
+    FocusTree(Vec<String>),
+    ToggleTreeCollapse(usize),
+    OpenSubtree(usize),
+    ShowFullTree,
 
     ChangedSearchFieldType(usize, String),
     ChangedSearchFieldFrom(usize, String),
@@ -73,9 +105,37 @@ pub enum MsgCore {
     DownloadFASTAgot(Vec<u8>),
 
     SetStrainSelected(String, bool),
+    SetStrainsSelected(Vec<String>, bool),
+
+    StartSketchSearch(File),
+    SetSketchHits(AsyncData<SketchSearchResult>),
+    SetSketchNumHits(usize),
+
+    ComputeDistanceMatrix,
+    SetDistanceMatrix(AsyncData<DistanceMatrix>),
+    DownloadDistanceMatrix,
 
     HideColumn(String),
     ShowColumn(String),
+
+    SetStatsFollowSearch(bool),
+    FetchSearchStats,
+    SetSearchStats(AsyncData<SearchStats>),
+
+    SetCrossTabRow(String),
+    SetCrossTabCol(String),
+    SetCrossTabMode(CrossTabMode),
+    ComputeCrossTab,
+    SetCrossTab(AsyncData<CrossTab>),
+    DownloadCrossTab,
+
+    SetScatterX(String),
+    SetScatterY(String),
+    SetScatterColor(String),
+
+    SetPointMapColor(String),
+    FetchPointMap,
+    SetPointMap(AsyncData<PointMap>),
 }
 
 
@@ -91,15 +151,51 @@ pub struct Model {
     pub search_settings: SearchSettings,
     pub db_metadata: Option<DatabaseMetadata>,
 
-    pub geojson: GeoJson,
+    pub geojson: Rc<GeoJson>,
 
     pub selected_strains: HashSet<String>,
+    /// Increased whenever the selection changes, so that views can tell without comparing the whole set
+    pub selection_version: u64,
 
     pub show_columns: HashSet<String>,
 
     pub last_component_size: ComponentSize,
 
     pub treedata: AsyncData<TreeLayout>,
+    /// The whole tree, while a clade of it is shown in treedata
+    pub treedata_full: AsyncData<TreeLayout>,
+    pub tree_is_subtree: bool,
+    pub tree_metadata: AsyncData<ColumnValues>,
+    pub tree_label_column: String,
+    pub tree_color_column: String,
+    pub tree_shape: TreeShape,
+    pub tree_cladogram: bool,
+    pub tree_find: String,
+    pub tree_focus: AsyncData<Vec<String>>,
+
+    pub sketch_hits: AsyncData<SketchSearchResult>,
+    pub sketch_num_hits: usize,
+
+    pub distmatrix: AsyncData<DistanceMatrix>,
+
+    pub releases: Option<ReleaseList>,
+    pub current_release: String,
+
+    pub last_search: Option<SearchSettings>,
+    pub stats_follow_search: bool,
+    pub search_stats: AsyncData<SearchStats>,
+
+    pub crosstab_row: String,
+    pub crosstab_col: String,
+    pub crosstab_mode: CrossTabMode,
+    pub crosstab: AsyncData<CrossTab>,
+
+    pub scatter_x: String,
+    pub scatter_y: String,
+    pub scatter_color: String,
+
+    pub pointmap_color: String,
+    pub pointmap: AsyncData<PointMap>,
 }
 
 impl Component for Model {
@@ -116,6 +212,7 @@ impl Component for Model {
 
         //Get metadata about database right away
         ctx.link().send_message(MsgCore::FetchDatabaseMetadata);
+        ctx.link().send_message(MsgCore::FetchReleases);
 
         //Get tree right away (or wait until tab open?)
         //ctx.link().send_message(MsgCore::FetchTreeData);
@@ -128,16 +225,49 @@ impl Component for Model {
             show_search_controls: true,
             search_settings: SearchSettings::new(),
             db_metadata: None,
-            geojson: geojson,
+            geojson: Rc::new(geojson),
 
             selected_strains: HashSet::new(),
+            selection_version: 0,
 
             show_columns: HashSet::new(),
 
             last_component_size: ComponentSize { width: 100.0, height: 100.0 },
             
             treedata: AsyncData::NotLoaded,
-
+            treedata_full: AsyncData::NotLoaded,
+            tree_is_subtree: false,
+            tree_metadata: AsyncData::NotLoaded,
+            tree_label_column: "".to_string(),
+            tree_color_column: "".to_string(),
+            tree_shape: TreeShape::Rectangular,
+            tree_cladogram: false,
+            tree_find: "".to_string(),
+            tree_focus: AsyncData::NotLoaded,
+
+            sketch_hits: AsyncData::NotLoaded,
+            sketch_num_hits: 20,
+
+            distmatrix: AsyncData::NotLoaded,
+
+            releases: None,
+            current_release: "".to_string(),
+
+            last_search: None,
+            stats_follow_search: false,
+            search_stats: AsyncData::NotLoaded,
+
+            crosstab_row: "".to_string(),
+            crosstab_col: "".to_string(),
+            crosstab_mode: CrossTabMode::Counts,
+            crosstab: AsyncData::NotLoaded,
+
+            scatter_x: "".to_string(),
+            scatter_y: "".to_string(),
+            scatter_color: "".to_string(),
+
+            pointmap_color: "".to_string(),
+            pointmap: AsyncData::NotLoaded,
         }
     }
 
@@ -172,12 +302,19 @@ impl Component for Model {
                 //Set "loading" placeholder
                 ctx.link().send_message(MsgCore::SetQuery(AsyncData::Loading));
 
+                //Statistics follow the search just run
+                self.last_search = Some(self.search_settings.clone());
+                self.search_stats = AsyncData::NotLoaded;
+                if self.stats_follow_search {
+                    ctx.link().send_message(MsgCore::FetchSearchStats);
+                }
+
                 //Start query
                 let json = serde_json::to_string(&self.search_settings).expect("Failed to generate json");
                 //log::debug!("sending {}", json);
-                async fn get_data(json: String) -> MsgCore {
+                async fn get_data(url: String, json: String) -> MsgCore {
                     let client = reqwest::Client::new();
-                    let res: TableData = client.post(format!("{}/straindata",get_host_url()))
+                    let res: TableData = client.post(url)
                         .header("Content-Type", "application/json")
                         .body(json)
                         .send()
@@ -189,7 +326,7 @@ impl Component for Model {
                     MsgCore::SetQuery(AsyncData::new(res))
                 }
 
-                ctx.link().send_future(get_data(json));
+                ctx.link().send_future(get_data(self.api_url("straindata"), json));
                 false
             }
 
@@ -197,12 +334,45 @@ impl Component for Model {
 
 
 
+            ////////////////////////////////////////////////////////////
+            // Message: Show given strains in the table, in the given order
+            MsgCore::StartQueryByID(list_strains) => {
+
+                //Set "loading" placeholder
+                ctx.link().send_message(MsgCore::SetQuery(AsyncData::Loading));
+
+                //The table no longer shows the result of a search
+                self.last_search = None;
+                self.search_stats = AsyncData::NotLoaded;
+
+                let req = StrainRequest {
+                    list: list_strains
+                };
+                let json = serde_json::to_string(&req).expect("Failed to generate json");
+                async fn get_data(url: String, json: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res: TableData = client.post(url)
+                        .header("Content-Type", "application/json")
+                        .body(json)
+                        .send()
+                        .await
+                        .expect("Failed to send request")
+                        .json()
+                        .await
+                        .expect("Failed to get table data");
+                    MsgCore::SetQuery(AsyncData::new(res))
+                }
+
+                ctx.link().send_future(get_data(self.api_url("straindata_by_id"), json));
+                false
+            }
+
+
             ////////////////////////////////////////////////////////////
             // x
             MsgCore::FetchDatabaseMetadata => {
-                async fn get_data() -> MsgCore {
+                async fn get_data(url: String) -> MsgCore {
                     let client = reqwest::Client::new();
-                    let url=format!("{}/strainmeta",get_host_url());
                     //log::debug!("wtf -{}-",url);
                     let res: DatabaseMetadata = client.get(url)  
                         .header("Content-Type", "application/json")
@@ -217,36 +387,84 @@ impl Component for Model {
                     MsgCore::SetDatabaseMetadata(res)
                 }
 
-                ctx.link().send_future(get_data());
+                ctx.link().send_future(get_data(self.api_url("strainmeta")));
+                false
+            }
+
+
+            ////////////////////////////////////////////////////////////
+            // Message: Get list of database releases
+            MsgCore::FetchReleases => {
+                async fn get_data(url: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res: ReleaseList = client.get(url)
+                        .send()
+                        .await
+                        .expect("Failed to send request")
+                        .json()
+                        .await
+                        .expect("Failed to get releases");
+                    MsgCore::SetReleases(res)
+                }
+
+                ctx.link().send_future(get_data(format!("{}/releases", get_host_url())));
                 false
             }
 
 
+            ////////////////////////////////////////////////////////////
+            // Message: Got list of database releases
+            MsgCore::SetReleases(list) => {
+                if self.current_release == "" {
+                    self.current_release = list.default_release.clone();
+                }
+                self.releases = Some(list);
+                true
+            }
+
+
+            ////////////////////////////////////////////////////////////
+            // Message: Switch to another database release. Everything loaded so far belongs to the old release
+            MsgCore::SetRelease(name) => {
+                self.current_release = name;
+                self.db_metadata = None;
+                self.tabledata = AsyncData::NotLoaded;
+                self.tabledata_from = 0;
+                self.treedata = AsyncData::NotLoaded;
+                self.treedata_full = AsyncData::NotLoaded;
+                self.tree_is_subtree = false;
+                self.tree_metadata = AsyncData::NotLoaded;
+                self.selected_strains.clear();
+                self.selection_version += 1;
+                self.sketch_hits = AsyncData::NotLoaded;
+                self.distmatrix = AsyncData::NotLoaded;
+                self.last_search = None;
+                self.search_stats = AsyncData::NotLoaded;
+                self.crosstab = AsyncData::NotLoaded;
+                self.pointmap = AsyncData::NotLoaded;
+                ctx.link().send_message(MsgCore::FetchDatabaseMetadata);
+                true
+            }
+
+
             ////////////////////////////////////////////////////////////
             // x
             MsgCore::FetchTreeData => {
-                async fn get_data() -> MsgCore {
+                async fn get_data(url: String) -> MsgCore {
                     let client = reqwest::Client::new();
-                    let url=format!("{}/treedata",get_host_url());
-                    //log::debug!("wtf -{}-",url);
                     log::debug!("getting tree");
-                    let res: TreeData = client.get(url)  
-                        .header("Content-Type", "application/json")
-                        .body("")
-                        //no body
+                    let res = client.get(url)
                         .send()
                         .await
                         .expect("Failed to send request")
-                        .json()
+                        .bytes()
                         .await
-                        .expect("Failed to get treedata");
-                    log::debug!("making layout");
-                    let lay = TreeLayout::new(&res.tree_str);
-                    log::debug!("setting layout");
-                    MsgCore::SetTreeData(lay)
+                        .expect("Failed to get tree layout");
+                    let data = TreeLayoutData::from_bytes(&res).expect("Failed to decode tree layout");
+                    MsgCore::SetTreeData(TreeLayout::from_data(data))
                 }
 
-                ctx.link().send_future(get_data());
+                ctx.link().send_future(get_data(self.api_url("treelayout")));
                 false
             }
 
@@ -259,7 +477,14 @@ impl Component for Model {
 
                 //log::trace!("SetDatabaseMetadata: {:?}", data);
                 //let lay = TreeLayout::new(&data.tree_str);
+                let mut lay = lay;
+                if self.tree_shape != TreeShape::Rectangular || self.tree_cladogram {
+                    lay.set_layout(self.tree_shape, self.tree_cladogram);
+                }
                 self.treedata = AsyncData::new(lay);
+                self.treedata_full = self.treedata.clone();
+                self.tree_is_subtree = false;
+                ctx.link().send_message(MsgCore::FetchTreeMetadata);
 
                 true
             }
@@ -272,6 +497,7 @@ impl Component for Model {
                 self.tabledata = data;
                 self.tabledata_from = 0;
                 self.selected_strains.clear();
+                self.selection_version += 1;
                 true
             }
 
@@ -284,7 +510,7 @@ impl Component for Model {
                 //Set columns to show
                 self.show_columns.clear();
                 for (colname, colmeta) in &data.columns {
-                    if colmeta.default_show_column=="1" {
+                    if colmeta.default_show_column {
                         self.show_columns.insert(colname.clone());
                     }
                 }
@@ -326,6 +552,21 @@ impl Component for Model {
                 true
             },
 
+            ////////////////////////////////////////////////////////////
+            // Search for strains with a given value in a column, e.g. after clicking a country on the map
+            MsgCore::SearchByValue(column, value) => {
+                //Replace any criterion on the same column, so that clicking another value moves the search there
+                self.search_settings.criteria.retain(|c| c.field != column);
+                let mut c = SearchCriteria::new();
+                c.field = column;
+                c.comparison = ComparisonType::Like(value);
+                self.search_settings.criteria.push(c);
+                self.show_search_controls = true;
+                self.current_page = CurrentPage::Search;
+                ctx.link().send_message(MsgCore::StartQuery);
+                true
+            },
+
             ////////////////////////////////////////////////////////////
             // x
             MsgCore::DeleteSearchFilter(i) => {
@@ -420,9 +661,9 @@ impl Component for Model {
                     //log::debug!("sending {}", json);
 
                     //log::debug!("sending {}", json);
-                    async fn get_data(json: String) -> MsgCore {
+                    async fn get_data(url: String, json: String) -> MsgCore {
                         let client = reqwest::Client::new();
-                        let res = client.post(format!("{}/strainfasta",get_host_url()))
+                        let res = client.post(url)
                             .header("Content-Type", "application/json")
                             .body(json)
                             .send()
@@ -434,7 +675,7 @@ impl Component for Model {
 
                         MsgCore::DownloadFASTAgot(res.to_vec())
                     }
-                    ctx.link().send_future(get_data(json));
+                    ctx.link().send_future(get_data(self.api_url("strainfasta"), json));
                 }        
                 false        
             },
@@ -465,9 +706,412 @@ impl Component for Model {
                 } else {
                     self.selected_strains.remove(&id);
                 }
+                self.selection_version += 1;
                 false
             },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Select or unselect several strains, e.g. from a plot
+            MsgCore::SetStrainsSelected(list, tosel) => {
+                for id in list {
+                    if tosel {
+                        self.selected_strains.insert(id);
+                    } else {
+                        self.selected_strains.remove(&id);
+                    }
+                }
+                self.selection_version += 1;
+                true
+            },
             
+            ////////////////////////////////////////////////////////////
+            // Message: Upload a genome and find the closest strains
+            MsgCore::StartSketchSearch(file) => {
+                self.sketch_hits = AsyncData::Loading;
+
+                async fn get_data(url: String, file: File, num_hits: usize) -> MsgCore {
+                    let content = JsFuture::from(file.array_buffer()).await.expect("Failed to read file");
+                    let content = Uint8Array::new(&content).to_vec();
+
+                    let client = reqwest::Client::new();
+                    let res = client.post(format!("{}&n={}", url, num_hits))
+                        .body(content)
+                        .send()
+                        .await
+                        .expect("Failed to send request");
+                    if !res.status().is_success() {
+                        alert(&res.text().await.unwrap_or_default());
+                        return MsgCore::SetSketchHits(AsyncData::NotLoaded);
+                    }
+                    let res: SketchSearchResult = res
+                        .json()
+                        .await
+                        .expect("Failed to get sketch hits");
+                    MsgCore::SetSketchHits(AsyncData::new(res))
+                }
+
+                ctx.link().send_future(get_data(self.api_url("sketchsearch"), file, self.sketch_num_hits));
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got closest strains to an uploaded genome. Show them in the table
+            MsgCore::SetSketchHits(hits) => {
+                if let AsyncData::Loaded(hits) = &hits {
+                    let list_strains = hits.hits.iter().map(|h| h.id.clone()).collect();
+                    ctx.link().send_message(MsgCore::StartQueryByID(list_strains));
+                }
+                self.sketch_hits = hits;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Set number of strains to report in sketch search
+            MsgCore::SetSketchNumHits(n) => {
+                self.sketch_num_hits = n;
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Compute pairwise distances between selected strains
+            MsgCore::ComputeDistanceMatrix => {
+                let list_strains = self.get_strains(&IncludeData::Selected);
+                if list_strains.len() < 2 {
+                    alert("Select at least two strains");
+                    return false;
+                }
+                self.distmatrix = AsyncData::Loading;
+
+                let req = StrainRequest {
+                    list: list_strains
+                };
+                let json = serde_json::to_string(&req).expect("Failed to generate json");
+                async fn get_data(url: String, json: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res = client.post(url)
+                        .header("Content-Type", "application/json")
+                        .body(json)
+                        .send()
+                        .await
+                        .expect("Failed to send request");
+                    if !res.status().is_success() {
+                        alert(&res.text().await.unwrap_or_default());
+                        return MsgCore::SetDistanceMatrix(AsyncData::NotLoaded);
+                    }
+                    let res: DistanceMatrix = res
+                        .json()
+                        .await
+                        .expect("Failed to get distance matrix");
+                    MsgCore::SetDistanceMatrix(AsyncData::new(res))
+                }
+                ctx.link().send_future(get_data(self.api_url("distmatrix"), json));
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got pairwise distances
+            MsgCore::SetDistanceMatrix(mat) => {
+                self.distmatrix = mat;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Show statistics for the whole database, or only for the last search
+            MsgCore::SetStatsFollowSearch(follow) => {
+                self.stats_follow_search = follow;
+                if follow && self.search_stats == AsyncData::NotLoaded {
+                    ctx.link().send_message(MsgCore::FetchSearchStats);
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Compute statistics for the strains matching the last search
+            MsgCore::FetchSearchStats => {
+                if let Some(search) = &self.last_search {
+                    self.search_stats = AsyncData::Loading;
+                    let json = serde_json::to_string(search).expect("Failed to generate json");
+                    async fn get_data(url: String, json: String) -> MsgCore {
+                        let client = reqwest::Client::new();
+                        let res: SearchStats = client.post(url)
+                            .header("Content-Type", "application/json")
+                            .body(json)
+                            .send()
+                            .await
+                            .expect("Failed to send request")
+                            .json()
+                            .await
+                            .expect("Failed to get search statistics");
+                        MsgCore::SetSearchStats(AsyncData::new(res))
+                    }
+                    ctx.link().send_future(get_data(self.api_url("searchstats"), json));
+                    true
+                } else {
+                    false
+                }
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got statistics for the last search
+            MsgCore::SetSearchStats(stats) => {
+                self.search_stats = stats;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Pick columns and cell content of the cross-tabulation
+            MsgCore::SetCrossTabRow(col) => {
+                self.crosstab_row = col;
+                true
+            },
+            MsgCore::SetCrossTabCol(col) => {
+                self.crosstab_col = col;
+                true
+            },
+            MsgCore::SetCrossTabMode(mode) => {
+                self.crosstab_mode = mode;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Cross-tabulate the chosen columns; over the last search if the statistics follow it
+            MsgCore::ComputeCrossTab => {
+                let req = CrossTabRequest {
+                    row_column: self.crosstab_row.clone(),
+                    col_column: self.crosstab_col.clone(),
+                    search: if self.stats_follow_search { self.last_search.clone() } else { None },
+                };
+                self.crosstab = AsyncData::Loading;
+                let json = serde_json::to_string(&req).expect("Failed to generate json");
+                async fn get_data(url: String, json: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res = client.post(url)
+                        .header("Content-Type", "application/json")
+                        .body(json)
+                        .send()
+                        .await
+                        .expect("Failed to send request");
+                    if !res.status().is_success() {
+                        alert(&res.text().await.unwrap_or_default());
+                        return MsgCore::SetCrossTab(AsyncData::NotLoaded);
+                    }
+                    let res: CrossTab = res
+                        .json()
+                        .await
+                        .expect("Failed to get cross-tabulation");
+                    MsgCore::SetCrossTab(AsyncData::new(res))
+                }
+                ctx.link().send_future(get_data(self.api_url("crosstab"), json));
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got cross-tabulation
+            MsgCore::SetCrossTab(table) => {
+                self.crosstab = table;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Save cross-tabulation as TSV
+            MsgCore::DownloadCrossTab => {
+                if let AsyncData::Loaded(table) = &self.crosstab {
+                    download_text_file("crosstab.tsv", "text/tab-separated-values", &table.to_tsv());
+                }
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Get metadata of all strains, to label the tree and for tooltips
+            MsgCore::FetchTreeMetadata => {
+                let req = ColumnValuesRequest {
+                    columns: self.tree_metadata_columns(),
+                };
+                let json = serde_json::to_string(&req).expect("Failed to generate json");
+                async fn get_data(url: String, json: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res: ColumnValues = client.post(url)
+                        .header("Content-Type", "application/json")
+                        .body(json)
+                        .send()
+                        .await
+                        .expect("Failed to send request")
+                        .json()
+                        .await
+                        .expect("Failed to get column values");
+                    MsgCore::SetTreeMetadata(AsyncData::new(res))
+                }
+                ctx.link().send_future(get_data(self.api_url("columnvalues"), json));
+                false
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got metadata for the tree
+            MsgCore::SetTreeMetadata(values) => {
+                self.tree_metadata = values;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Pick column to label tips by. Get its values unless already loaded
+            MsgCore::SetTreeLabelColumn(col) => {
+                self.tree_label_column = col;
+                if !self.has_tree_metadata(&self.tree_label_column) {
+                    ctx.link().send_message(MsgCore::FetchTreeMetadata);
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Pick column to color the tree by. Get its values unless already loaded
+            MsgCore::SetTreeColorColumn(col) => {
+                self.tree_color_column = col;
+                if !self.has_tree_metadata(&self.tree_color_column) {
+                    ctx.link().send_message(MsgCore::FetchTreeMetadata);
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Change the shape of the tree. The tree already loaded is laid out again
+            MsgCore::SetTreeLayout(shape, cladogram) => {
+                self.tree_shape = shape;
+                self.tree_cladogram = cladogram;
+                if let AsyncData::Loaded(treedata) = &self.treedata {
+                    self.treedata = AsyncData::new(treedata.with_layout(shape, cladogram));
+                    if !self.tree_is_subtree {
+                        self.treedata_full = self.treedata.clone();
+                    }
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Collapse a clade into a triangle, or expand it
+            MsgCore::ToggleTreeCollapse(id) => {
+                if let AsyncData::Loaded(treedata) = &self.treedata {
+                    self.treedata = AsyncData::new(treedata.with_collapse_toggled(id));
+                    if !self.tree_is_subtree {
+                        self.treedata_full = self.treedata.clone();
+                    }
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Show only a clade, as a tree of its own
+            MsgCore::OpenSubtree(id) => {
+                if let AsyncData::Loaded(treedata) = &self.treedata {
+                    self.treedata = AsyncData::new(treedata.subtree(id));
+                    self.tree_is_subtree = true;
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Go back from a clade to the whole tree, in the current shape
+            MsgCore::ShowFullTree => {
+                if let AsyncData::Loaded(full) = &self.treedata_full {
+                    self.treedata = if full.shape == self.tree_shape && full.cladogram == self.tree_cladogram {
+                        self.treedata_full.clone()
+                    } else {
+                        AsyncData::new(full.with_layout(self.tree_shape, self.tree_cladogram))
+                    };
+                    self.treedata_full = self.treedata.clone();
+                    self.tree_is_subtree = false;
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Find strains in the tree by ID or metadata value, and zoom to them
+            MsgCore::FindInTree(query) => {
+                let list_found = self.find_in_tree(&query);
+                self.tree_find = query;
+                self.tree_focus = AsyncData::new(list_found);
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Zoom the tree to some strains, and highlight them
+            MsgCore::FocusTree(list_strains) => {
+                self.tree_find = "".to_string();
+                self.tree_focus = AsyncData::new(list_strains);
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Pick columns of the scatter plot
+            MsgCore::SetScatterX(col) => {
+                self.scatter_x = col;
+                true
+            },
+            MsgCore::SetScatterY(col) => {
+                self.scatter_y = col;
+                true
+            },
+            MsgCore::SetScatterColor(col) => {
+                self.scatter_color = col;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Pick column to color isolate locations by. Reload if already shown
+            MsgCore::SetPointMapColor(col) => {
+                self.pointmap_color = col;
+                if self.pointmap != AsyncData::NotLoaded {
+                    ctx.link().send_message(MsgCore::FetchPointMap);
+                }
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Get isolate locations; over the last search if the statistics follow it
+            MsgCore::FetchPointMap => {
+                let req = PointMapRequest {
+                    color_column: self.pointmap_color.clone(),
+                    search: if self.stats_follow_search { self.last_search.clone() } else { None },
+                };
+                self.pointmap = AsyncData::Loading;
+                let json = serde_json::to_string(&req).expect("Failed to generate json");
+                async fn get_data(url: String, json: String) -> MsgCore {
+                    let client = reqwest::Client::new();
+                    let res = client.post(url)
+                        .header("Content-Type", "application/json")
+                        .body(json)
+                        .send()
+                        .await
+                        .expect("Failed to send request");
+                    if !res.status().is_success() {
+                        alert(&res.text().await.unwrap_or_default());
+                        return MsgCore::SetPointMap(AsyncData::NotLoaded);
+                    }
+                    let res: PointMap = res
+                        .json()
+                        .await
+                        .expect("Failed to get isolate locations");
+                    MsgCore::SetPointMap(AsyncData::new(res))
+                }
+                ctx.link().send_future(get_data(self.api_url("pointmap"), json));
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Got isolate locations
+            MsgCore::SetPointMap(points) => {
+                self.pointmap = points;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Save pairwise distances as TSV
+            MsgCore::DownloadDistanceMatrix => {
+                if let AsyncData::Loaded(mat) = &self.distmatrix {
+                    download_text_file("distances.tsv", "text/tab-separated-values", &mat.to_tsv());
+                }
+                false
+            },
+
             ////////////////////////////////////////////////////////////
             // Hide a column specified by name
             MsgCore::HideColumn(col) => {
@@ -545,6 +1189,7 @@ impl Component for Model {
 
                 <header class="App-header">
                     <div id="topmenu" class="topnav">
+                        { self.view_release_selector(&ctx) }
                         <div class="topnav-right">
                             <a class={active_if(self.current_page==CurrentPage::Home)}       onclick={ctx.link().callback(|_| MsgCore::OpenPage(CurrentPage::Home))}>{"Home"}</a> 
                             <a class={active_if(self.current_page==CurrentPage::Search)}     onclick={ctx.link().callback(|_| MsgCore::OpenPage(CurrentPage::Search))}>{"Search"}</a>
@@ -573,6 +1218,18 @@ impl Component for Model {
 
 
 
+impl Model {
+
+    ////////////////////////////////////////////////////////////
+    /// URL of a REST entry point, for the current database release
+    pub fn api_url(&self, path: &str) -> String {
+        format!("{}/{}?release={}", get_host_url(), path, String::from(web_sys::js_sys::encode_uri_component(&self.current_release)))
+    }
+
+}
+
+
+
 ////////////////////////////////////////////////////////////
 /// If condition is met, return "active", otherwise "". For CSS styling of which control is active
 pub fn active_if(cond: bool) -> String {
@@ -1,9 +1,10 @@
 use std::collections::HashSet;
 
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use web_sys::js_sys::{Array, Uint8Array};
 use web_sys::{wasm_bindgen::JsValue, Blob, BlobPropertyBag};
-use web_sys::{HtmlElement};
+use web_sys::{CanvasRenderingContext2d, Element, HtmlCanvasElement, HtmlElement, HtmlImageElement};
 use web_sys::window;
 
 use crate::appstate::AsyncData;
@@ -68,44 +69,8 @@ impl Model {
     ////////////////////////////////////////////////////////////
     /// Download table data
     pub fn download_metadata(&self, list_strains: &Vec<String>){
-
-        let window = window().expect("no window");
-        let document = window.document().expect("should have a document on window");
-        //let body = document.body().expect("document should have a body");
-
         let csv_data = self.make_metadata_csv(list_strains);
-
-        // Creating a Blob for having a csv file format and passing the data with type
-        // https://docs.rs/web-sys/latest/web_sys/struct.Blob.html
-        // const blob = new Blob([data], { type: 'text/csv' }); 
-        let blob_properties = BlobPropertyBag::new();
-        blob_properties.set_type("text/csv");  // application/zip     application/json
-
-        let blob_parts = Array::new();
-        blob_parts.push(&JsValue::from_str(csv_data.as_str()));
-        let blob = Blob::new_with_buffer_source_sequence_and_options(&blob_parts, &blob_properties).unwrap();
-
-        // Creating an object for downloading url
-        // const url = window.URL.createObjectURL(blob)
-        let url = web_sys::Url::create_object_url_with_blob(&blob).expect("Could not create url");
-
-        // Creating an anchor(a) tag of HTML
-        // const a = document.createElement('a')
-        let a:HtmlElement = document.create_element("a").expect("could not create a").dyn_into().unwrap();
-
-        // Passing the blob downloading url 
-        // a.setAttribute('href', url)
-        a.set_attribute("href", &url).expect("Could not set attribute");
-
-        // Setting the anchor tag attribute for downloading
-        // and passing the download file name
-        // a.setAttribute('download', 'download.csv');    
-    //    a.set_attribute("download", "fastq.zip").expect("Could not set attribute");
-        a.set_attribute("download", "metadata.txt").expect("Could not set attribute");
-
-        // Performing a download with click
-        // a.click()
-        a.click();
+        download_text_file("metadata.txt", "text/csv", &csv_data);
     }
 
 
@@ -172,6 +137,132 @@ impl Model {
 
 
 
+////////////////////////////////////////////////////////////
+/// Let the user save a text file, generated in the browser
+pub fn download_text_file(filename: &str, mimetype: &str, content: &str) {
+
+    // Creating a Blob for having a csv file format and passing the data with type
+    // https://docs.rs/web-sys/latest/web_sys/struct.Blob.html
+    // const blob = new Blob([data], { type: 'text/csv' }); 
+    let blob_properties = BlobPropertyBag::new();
+    blob_properties.set_type(mimetype);  // application/zip     application/json
+
+    let blob_parts = Array::new();
+    blob_parts.push(&JsValue::from_str(content));
+    let blob = Blob::new_with_buffer_source_sequence_and_options(&blob_parts, &blob_properties).unwrap();
+
+    download_blob(filename, &blob);
+}
+
+
+////////////////////////////////////////////////////////////
+/// Let the user save a blob
+fn download_blob(filename: &str, blob: &Blob) {
+
+    let window = window().expect("no window");
+    let document = window.document().expect("should have a document on window");
+
+    // Creating an object for downloading url
+    // const url = window.URL.createObjectURL(blob)
+    let url = web_sys::Url::create_object_url_with_blob(blob).expect("Could not create url");
+
+    // Creating an anchor(a) tag of HTML
+    // const a = document.createElement('a')
+    let a:HtmlElement = document.create_element("a").expect("could not create a").dyn_into().unwrap();
+
+    // Passing the blob downloading url 
+    // a.setAttribute('href', url)
+    a.set_attribute("href", &url).expect("Could not set attribute");
+
+    // Setting the anchor tag attribute for downloading
+    // and passing the download file name
+    a.set_attribute("download", filename).expect("Could not set attribute");
+
+    // Performing a download with click
+    // a.click()
+    a.click();
+}
+
+
+/// PNG files are rendered at this many pixels per screen pixel
+const PNG_SCALE: f64 = 2.0;
+
+
+////////////////////////////////////////////////////////////
+/// Get the first SVG inside an element, as a standalone SVG file. Also returns its size on screen
+fn standalone_svg(element_id: &str) -> Option<(String, f64, f64)> {
+    let document = window()?.document()?;
+    let svg = document.get_element_by_id(element_id)?.query_selector("svg").ok()??;
+    let rect = svg.get_bounding_client_rect();
+
+    //Work on a copy, so the page is not affected
+    let svg: Element = svg.clone_node_with_deep(true).ok()?.dyn_into().ok()?;
+    svg.set_attribute("xmlns", "http://www.w3.org/2000/svg").ok()?;
+    svg.set_attribute("width", &format!("{}", rect.width())).ok()?;
+    svg.set_attribute("height", &format!("{}", rect.height())).ok()?;
+
+    let content = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", svg.outer_html());
+    Some((content, rect.width(), rect.height()))
+}
+
+
+////////////////////////////////////////////////////////////
+/// Let the user save the first SVG inside an element
+pub fn download_svg(element_id: &str, filename: &str) {
+    if let Some((content, _, _)) = standalone_svg(element_id) {
+        download_text_file(filename, "image/svg+xml", &content);
+    } else {
+        log::debug!("no svg in {}", element_id);
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Let the user save the first SVG inside an element as PNG. The SVG is drawn on a canvas
+/// once loaded as an image; saving hence happens later
+pub fn download_svg_as_png(element_id: &str, filename: &str) {
+    let (content, w, h) = if let Some(svg) = standalone_svg(element_id) {
+        svg
+    } else {
+        log::debug!("no svg in {}", element_id);
+        return;
+    };
+
+    let blob_properties = BlobPropertyBag::new();
+    blob_properties.set_type("image/svg+xml");
+    let blob_parts = Array::new();
+    blob_parts.push(&JsValue::from_str(&content));
+    let blob = Blob::new_with_buffer_source_sequence_and_options(&blob_parts, &blob_properties).unwrap();
+    let url = web_sys::Url::create_object_url_with_blob(&blob).expect("Could not create url");
+
+    let img = HtmlImageElement::new().expect("Could not create image");
+    let img_loaded = img.clone();
+    let url_loaded = url.clone();
+    let filename = filename.to_string();
+    let onload = Closure::once_into_js(move || {
+        let document = window().expect("no window").document().expect("should have a document on window");
+        let canvas: HtmlCanvasElement = document.create_element("canvas").expect("could not create canvas").dyn_into().unwrap();
+        canvas.set_width((w * PNG_SCALE) as u32);
+        canvas.set_height((h * PNG_SCALE) as u32);
+
+        let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
+        context.set_fill_style_str("white");
+        context.fill_rect(0.0, 0.0, w * PNG_SCALE, h * PNG_SCALE);
+        context.draw_image_with_html_image_element_and_dw_and_dh(&img_loaded, 0.0, 0.0, w * PNG_SCALE, h * PNG_SCALE).expect("Could not draw image");
+        web_sys::Url::revoke_object_url(&url_loaded).ok();
+
+        let on_blob = Closure::once_into_js(move |blob: JsValue| {
+            if let Ok(blob) = blob.dyn_into::<Blob>() {
+                download_blob(&filename, &blob);
+            }
+        });
+        canvas.to_blob(on_blob.unchecked_ref()).expect("Could not make PNG");
+    });
+    img.set_onload(Some(onload.unchecked_ref()));
+    img.set_src(&url);
+}
+
+
 
   /*
    * if we want to hack javascript, this is more scalable
@@ -5,7 +5,14 @@ pub mod model_search;
 pub mod model_stats;
 pub mod model_table;
 pub mod model_help;
+pub mod model_distmatrix;
+pub mod model_crosstab;
+pub mod model_scatter;
+pub mod model_pointmap;
 pub mod component_map;
+pub mod component_heatmap;
+pub mod component_scatter;
+pub mod component_pointmap;
 pub mod download;
 pub mod resize;
 pub mod treeview;
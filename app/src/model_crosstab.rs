@@ -0,0 +1,143 @@
+use crate::appstate::AsyncData;
+use crate::component_heatmap::svg_heatmap;
+use crate::core_model::*;
+
+use my_web_app::{ColumnType, CrossTab};
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlSelectElement};
+use yew::prelude::*;
+
+
+////////////////////////////////////////////////////////////
+/// What the cells of a cross-tabulation show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrossTabMode {
+    Counts,
+    RowPercent,
+    ColPercent,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Cell values (0..1) and cell texts of a cross-tabulation
+pub fn crosstab_cells(table: &CrossTab, mode: CrossTabMode) -> (Vec<Vec<f64>>, Vec<Vec<String>>) {
+    let row_totals = table.row_totals();
+    let col_totals = table.col_totals();
+    let max_count = table.counts.iter().flatten().cloned().max().unwrap_or(0);
+
+    let mut values = Vec::new();
+    let mut cell_text = Vec::new();
+    for (i, row) in table.counts.iter().enumerate() {
+        let mut row_values = Vec::new();
+        let mut row_text = Vec::new();
+        for (j, cnt) in row.iter().enumerate() {
+            let total = match mode {
+                CrossTabMode::Counts => max_count,
+                CrossTabMode::RowPercent => row_totals[i],
+                CrossTabMode::ColPercent => col_totals[j],
+            };
+            let frac = if total > 0 { *cnt as f64 / total as f64 } else { 0.0 };
+            row_values.push(frac);
+            row_text.push(match mode {
+                CrossTabMode::Counts => format!("{}", cnt),
+                _ => format!("{:.1}%", 100.0 * frac),
+            });
+        }
+        values.push(row_values);
+        cell_text.push(row_text);
+    }
+    (values, cell_text)
+}
+
+
+impl Model {
+
+    ////////////////////////////////////////////////////////////
+    /// Cross-tabulation of two categorical columns, as a heatmap
+    pub fn view_crosstab(&self, ctx: &Context<Self>) -> Html {
+
+        let db_metadata = if let Some(db_metadata) = &self.db_metadata {
+            db_metadata
+        } else {
+            return html! {""};
+        };
+
+        //Dropdown of categorical columns
+        let select_column = |current: &String, on_change: Callback<Event>| {
+            html! {
+                <select class="columndrop" onchange={on_change}>
+                    <option value="" selected={current.is_empty()}>{""}</option>
+                    {
+                        db_metadata.columns_by_category(|c| c.display && (c.column_type == ColumnType::Text || c.column_type == ColumnType::Boolean)).into_iter().map(|(category, list_col)| {
+                            html! {
+                                <optgroup label={category}>
+                                {
+                                    list_col.into_iter().map(|col| html! {
+                                        <option value={col.column_id.clone()} selected={&col.column_id == current} title={col.description.clone()}>
+                                            { col.display_name() }
+                                        </option>
+                                    }).collect::<Html>()
+                                }
+                                </optgroup>
+                            }
+                        }).collect::<Html>()
+                    }
+                </select>
+            }
+        };
+        let select_value = |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()).expect("wrong type");
+            input.value()
+        };
+        let onchange_row = ctx.link().callback(move |e: Event| MsgCore::SetCrossTabRow(select_value(e)));
+        let onchange_col = ctx.link().callback(move |e: Event| MsgCore::SetCrossTabCol(select_value(e)));
+        let onchange_mode = ctx.link().callback(move |e: Event| {
+            MsgCore::SetCrossTabMode(match select_value(e).as_str() {
+                "row" => CrossTabMode::RowPercent,
+                "col" => CrossTabMode::ColPercent,
+                _ => CrossTabMode::Counts,
+            })
+        });
+
+        let html_table = match &self.crosstab {
+            AsyncData::NotLoaded => html! {""},
+            AsyncData::Loading => html! { <div>{"Computing table..."}</div> },
+            AsyncData::Loaded(table) => {
+                let (values, cell_text) = crosstab_cells(table, self.crosstab_mode);
+                html! {
+                    <div>
+                        <button class="buttonspacer" onclick={ctx.link().callback(|_| MsgCore::DownloadCrossTab)}>
+                            {"Download TSV"}
+                        </button>
+                        <div style="overflow:auto;">
+                            { svg_heatmap(&table.row_names, &table.col_names, &values, &cell_text) }
+                        </div>
+                    </div>
+                }
+            }
+        };
+
+        let can_compute = !self.crosstab_row.is_empty() && !self.crosstab_col.is_empty();
+        html! {
+            <div>
+                <div>{"Cross-tabulation"}</div>
+                <span>{"Rows: "}</span>
+                { select_column(&self.crosstab_row, onchange_row) }
+                <span>{" Columns: "}</span>
+                { select_column(&self.crosstab_col, onchange_col) }
+                <span>{" Show: "}</span>
+                <select class="columndrop" onchange={onchange_mode}>
+                    <option value="count" selected={self.crosstab_mode == CrossTabMode::Counts}>{"Counts"}</option>
+                    <option value="row" selected={self.crosstab_mode == CrossTabMode::RowPercent}>{"Row %"}</option>
+                    <option value="col" selected={self.crosstab_mode == CrossTabMode::ColPercent}>{"Column %"}</option>
+                </select>
+                <button class="buttonspacer" disabled={!can_compute} onclick={ctx.link().callback(|_| MsgCore::ComputeCrossTab)}>
+                    {"Compute"}
+                </button>
+                { html_table }
+            </div>
+        }
+    }
+
+}
@@ -0,0 +1,61 @@
+use crate::appstate::AsyncData;
+use crate::component_heatmap::svg_heatmap;
+use crate::core_model::*;
+
+use yew::prelude::*;
+
+impl Model {
+
+    ////////////////////////////////////////////////////////////
+    /// Pairwise distances between selected strains, as a clustered heatmap
+    pub fn view_distance_matrix(&self, ctx: &Context<Self>) -> Html {
+
+        match &self.distmatrix {
+            AsyncData::NotLoaded => html! {""},
+            AsyncData::Loading => html! {
+                <div class="commontext">{"Computing distances..."}</div>
+            },
+            AsyncData::Loaded(mat) => {
+
+                //Show in clustered order. Closest pairs are the most intense
+                let names: Vec<String> = mat.order.iter().map(|i| mat.names[*i].clone()).collect();
+                let max_dist = mat.distance.iter().flatten().cloned().fold(0.0, f64::max);
+                let mut values = Vec::new();
+                let mut cell_text = Vec::new();
+                for i in &mat.order {
+                    let mut row_values = Vec::new();
+                    let mut row_text = Vec::new();
+                    for j in &mat.order {
+                        let d = mat.distance[*i][*j];
+                        row_values.push(if max_dist > 0.0 { 1.0 - d/max_dist } else { 1.0 });
+                        row_text.push(format!("{:.4}", d));
+                    }
+                    values.push(row_values);
+                    cell_text.push(row_text);
+                }
+
+                let html_missing = if mat.missing.is_empty() {
+                    html! {""}
+                } else {
+                    html! {
+                        <p>{format!("No sketch available for: {}", mat.missing.join(", "))}</p>
+                    }
+                };
+
+                html! {
+                    <div class="commontext">
+                        <div class="App-divider">{"Pairwise Mash distances of selected strains"}</div>
+                        <button class="buttonspacer" onclick={ctx.link().callback(|_| MsgCore::DownloadDistanceMatrix)}>
+                            {"Download TSV"}
+                        </button>
+                        { html_missing }
+                        <div style="overflow:auto;">
+                            { svg_heatmap(&names, &names, &values, &cell_text) }
+                        </div>
+                    </div>
+                }
+            }
+        }
+    }
+
+}
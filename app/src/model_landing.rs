@@ -1,5 +1,7 @@
 use crate::core_model::*;
 
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlSelectElement};
 use yew::prelude::*;
 
 
@@ -28,6 +30,17 @@ impl Model {
             "___".to_string()
         };
 
+        //The release is only worth naming if there is more than one
+        let html_release = if self.releases.as_ref().map(|list| list.releases.len() > 1).unwrap_or(false) {
+            html! {
+                <p style="color: rgb(0, 150, 255);">
+                    {format!("Database release {}", self.current_release)}
+                </p>
+            }
+        } else {
+            html! {""}
+        };
+
         html! {
 
             <div class="landingdiv">
@@ -38,9 +51,7 @@ impl Model {
                         {"A community curated, global atlas of Bacillus cereus group genomes"}
                     </p>
 
-                    <p style="color: rgb(0, 150, 255);">
-                        {"Database version v1"}
-                    </p>
+                    { html_release }
 
                     <p style="color: rgb(0, 150, 255);">
                         {num_strain} {" total B. cereus group genomes with curated metadata"}
@@ -49,6 +60,8 @@ impl Model {
                     <button class="toolbutton" onclick={ctx.link().callback(|_| MsgCore::OpenPage(CurrentPage::Search))}>
                         {"Search BTyperDB"}
                     </button>
+
+                    { self.view_release_history() }
                 </div>
             </div>
         }
@@ -56,4 +69,85 @@ impl Model {
 
 
 
+    ////////////////////////////////////////////////////////////
+    /// Selector of which database release to use. Only shown if there is more than one
+    pub fn view_release_selector(&self, ctx: &Context<Self>) -> Html {
+        if let Some(list) = &self.releases {
+            if list.releases.len() > 1 {
+
+                let onchange = ctx.link().callback(move |e: Event | {
+                    let target: Option<EventTarget> = e.target();
+                    let input = target.and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()).expect("wrong type");
+                    MsgCore::SetRelease(input.value())
+                });
+
+                return html! {
+                    <div class="topnav-left">
+                        <span class="commontext">{"Release: "}</span>
+                        <select class="columndrop" onchange={onchange}>
+                            {
+                                list.releases.iter().map(|r| {
+                                    html! {
+                                        <option value={r.name.clone()} selected={r.name == self.current_release}>
+                                            {r.name.clone()}
+                                        </option>
+                                    }
+                                }).collect::<Html>()
+                            }
+                        </select>
+                    </div>
+                };
+            }
+        }
+        html! {""}
+    }
+
+
+
+    ////////////////////////////////////////////////////////////
+    /// What changed between database releases, newest first
+    pub fn view_release_history(&self) -> Html {
+        if let Some(list) = &self.releases {
+            if list.releases.len() > 1 {
+                return html! {
+                    <div>
+                        <h2>{"Release history"}</h2>
+                        {
+                            list.releases.iter().rev().map(|r| {
+                                let html_diff = if let Some(diff) = &r.diff {
+                                    let mut list_changes = vec![
+                                        format!("{} genomes added, {} removed since {}", diff.strains_added, diff.strains_removed, diff.previous)
+                                    ];
+                                    if !diff.columns_added.is_empty() {
+                                        list_changes.push(format!("New columns: {}", diff.columns_added.join(", ").replace("_", " ")));
+                                    }
+                                    if !diff.columns_removed.is_empty() {
+                                        list_changes.push(format!("Removed columns: {}", diff.columns_removed.join(", ").replace("_", " ")));
+                                    }
+                                    html! {
+                                        <ul>
+                                            { list_changes.into_iter().map(|c| html!{<li>{c}</li>}).collect::<Html>() }
+                                        </ul>
+                                    }
+                                } else {
+                                    html! {""}
+                                };
+                                html! {
+                                    <div>
+                                        <b>{format!("{} ({} genomes)", r.name, r.num_strain)}</b>
+                                        {" "}{r.description.clone()}
+                                        {html_diff}
+                                    </div>
+                                }
+                            }).collect::<Html>()
+                        }
+                    </div>
+                };
+            }
+        }
+        html! {""}
+    }
+
+
+
 }
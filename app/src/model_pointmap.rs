@@ -0,0 +1,73 @@
+use crate::appstate::AsyncData;
+use crate::component_pointmap::PointMapView;
+use crate::core_model::*;
+
+use my_web_app::ColumnType;
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlSelectElement};
+use yew::prelude::*;
+
+impl Model {
+
+    ////////////////////////////////////////////////////////////
+    /// Map of isolate locations, if the database has coordinates
+    pub fn view_pointmap(&self, ctx: &Context<Self>) -> Html {
+
+        let db_metadata = if let Some(db_metadata) = &self.db_metadata {
+            db_metadata
+        } else {
+            return html! {""};
+        };
+        if db_metadata.location.is_none() {
+            return html! {""};
+        }
+
+        //Dropdown of categorical columns to color by
+        let onchange_color = ctx.link().callback(move |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()).expect("wrong type");
+            MsgCore::SetPointMapColor(input.value())
+        });
+        let html_select = html! {
+            <select class="columndrop" onchange={onchange_color}>
+                <option value="" selected={self.pointmap_color.is_empty()}>{""}</option>
+                {
+                    db_metadata.columns_by_category(|c| c.display && (c.column_type == ColumnType::Text || c.column_type == ColumnType::Boolean)).into_iter().map(|(category, list_col)| {
+                        html! {
+                            <optgroup label={category}>
+                            {
+                                list_col.into_iter().map(|col| html! {
+                                    <option value={col.column_id.clone()} selected={col.column_id == self.pointmap_color} title={col.description.clone()}>
+                                        { col.display_name() }
+                                    </option>
+                                }).collect::<Html>()
+                            }
+                            </optgroup>
+                        }
+                    }).collect::<Html>()
+                }
+            </select>
+        };
+
+        let html_map = match &self.pointmap {
+            AsyncData::NotLoaded => html! {""},
+            AsyncData::Loading => html! { <div>{"Loading locations..."}</div> },
+            AsyncData::Loaded(pointmap) => html! {
+                <PointMapView geojson={self.geojson.clone()} pointmap={pointmap.clone()}/>
+            },
+        };
+
+        html! {
+            <div>
+                <div>{"Isolate locations"}</div>
+                <span>{"Color by: "}</span>
+                { html_select }
+                <button class="buttonspacer" onclick={ctx.link().callback(|_| MsgCore::FetchPointMap)}>
+                    {"Show"}
+                </button>
+                { html_map }
+            </div>
+        }
+    }
+
+}
@@ -0,0 +1,89 @@
+use crate::appstate::AsyncData;
+use crate::component_scatter::ScatterView;
+use crate::core_model::*;
+
+use my_web_app::{ColumnType, DatabaseColumn};
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlSelectElement};
+use yew::prelude::*;
+
+impl Model {
+
+    ////////////////////////////////////////////////////////////
+    /// Scatter plot of two numeric columns of the search result
+    pub fn view_scatter(&self, ctx: &Context<Self>) -> Html {
+
+        let db_metadata = if let Some(db_metadata) = &self.db_metadata {
+            db_metadata
+        } else {
+            return html! {""};
+        };
+        if !matches!(self.tabledata, AsyncData::Loaded(_)) {
+            return html! {""};
+        }
+
+        //Dropdown of columns passing a filter
+        let select_column = |current: &String, filter: fn(&DatabaseColumn) -> bool, on_change: Callback<Event>| {
+            html! {
+                <select class="columndrop" onchange={on_change}>
+                    <option value="" selected={current.is_empty()}>{""}</option>
+                    {
+                        db_metadata.columns_by_category(|c| c.display && filter(c)).into_iter().map(|(category, list_col)| {
+                            html! {
+                                <optgroup label={category}>
+                                {
+                                    list_col.into_iter().map(|col| html! {
+                                        <option value={col.column_id.clone()} selected={&col.column_id == current} title={col.description.clone()}>
+                                            { col.display_name_with_unit() }
+                                        </option>
+                                    }).collect::<Html>()
+                                }
+                                </optgroup>
+                            }
+                        }).collect::<Html>()
+                    }
+                </select>
+            }
+        };
+        let select_value = |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()).expect("wrong type");
+            input.value()
+        };
+        let is_numeric: fn(&DatabaseColumn) -> bool = |c| c.column_type.is_numeric();
+        let is_categorical: fn(&DatabaseColumn) -> bool = |c| c.column_type == ColumnType::Text || c.column_type == ColumnType::Boolean;
+
+        //Callback: send message from the plot to this component
+        let on_propagate = ctx.link().callback(move |sig: MsgCore| sig);
+
+        let html_plot = if !self.scatter_x.is_empty() && !self.scatter_y.is_empty() {
+            html! {
+                <ScatterView
+                    on_propagate={on_propagate}
+                    tabledata={self.tabledata.clone()}
+                    selected_strains={self.selected_strains.clone()}
+                    selection_version={self.selection_version}
+                    col_x={self.scatter_x.clone()}
+                    col_y={self.scatter_y.clone()}
+                    col_color={self.scatter_color.clone()}
+                />
+            }
+        } else {
+            html! {""}
+        };
+
+        html! {
+            <div class="commontext">
+                <div class="App-divider">{"Scatter plot of search result"}</div>
+                <span>{"X: "}</span>
+                { select_column(&self.scatter_x, is_numeric, ctx.link().callback(move |e: Event| MsgCore::SetScatterX(select_value(e)))) }
+                <span>{" Y: "}</span>
+                { select_column(&self.scatter_y, is_numeric, ctx.link().callback(move |e: Event| MsgCore::SetScatterY(select_value(e)))) }
+                <span>{" Color by: "}</span>
+                { select_column(&self.scatter_color, is_categorical, ctx.link().callback(move |e: Event| MsgCore::SetScatterColor(select_value(e)))) }
+                { html_plot }
+            </div>
+        }
+    }
+
+}
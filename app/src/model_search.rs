@@ -1,3 +1,4 @@
+use crate::appstate::AsyncData;
 use crate::core_model::*;
 use wasm_bindgen::JsCast;
 
@@ -85,19 +86,22 @@ impl Model {
             }
         };
 
-        //Figure out which fields we can search
-        let mut list_select_options = Vec::new();
-        for (colname,colmeta) in &metadata.columns {
-            if colmeta.display {
-                list_select_options.push(
-                    html!{
-                        <option value={colname.clone()} selected={*colname == crit.field}>
-                            { colname.replace("_", " ") }
-                        </option>
-                    }
-                );
+        //Figure out which fields we can search, grouped by category
+        let list_select_options = metadata.columns_by_category(|c| c.display).into_iter().map(|(category, list_col)| {
+            html!{
+                <optgroup label={category}>
+                {
+                    list_col.into_iter().map(|col| {
+                        html!{
+                            <option value={col.column_id.clone()} selected={col.column_id == crit.field} title={col.description.clone()}>
+                                { col.display_name_with_unit() }
+                            </option>
+                        }
+                    }).collect::<Html>()
+                }
+                </optgroup>
             }
-        }
+        }).collect::<Html>();
 
         //HTML: all elements together
         html! {
@@ -116,6 +120,72 @@ impl Model {
 
 
 
+    ////////////////////////////////////////////////////////////
+    /// Upload of a genome to find the closest strains, and the resulting hits
+    pub fn view_sketch_search(&self, ctx: &Context<Self>) -> Html {
+
+        //Callback: A genome file has been picked
+        let onchange_file = ctx.link().batch_callback(move |e: Event | {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).expect("wrong type");
+            let file = input.files().and_then(|list| list.get(0));
+            input.set_value("");
+            file.map(|f| MsgCore::StartSketchSearch(f))
+        });
+
+        //Callback: Number of hits to report
+        let onchange_numhits = ctx.link().batch_callback(move |e: Event | {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).expect("wrong type");
+            input.value().parse::<usize>().ok().map(|n| MsgCore::SetSketchNumHits(n))
+        });
+
+        let html_hits = match &self.sketch_hits {
+            AsyncData::NotLoaded => html! {""},
+            AsyncData::Loading => html! {
+                <span class="commontext">{"Sketching genome..."}</span>
+            },
+            AsyncData::Loaded(hits) => html! {
+                <table class="divtable2">
+                    <tr>
+                        <th class="tableheader">{"BTyperDB ID"}</th>
+                        <th class="tableheader">{"Mash distance"}</th>
+                        <th class="tableheader">{"ANI estimate (%)"}</th>
+                        <th class="tableheader">{"Shared hashes"}</th>
+                    </tr>
+                    {
+                        hits.hits.iter().map(|h| {
+                            html! {
+                                <tr key={h.id.clone()}>
+                                    <td class="tablecontent">{h.id.clone()}</td>
+                                    <td class="tablecontent">{format!("{:.4}", h.distance)}</td>
+                                    <td class="tablecontent">{format!("{:.2}", h.ani)}</td>
+                                    <td class="tablecontent">{format!("{}/{}", h.shared_hashes, h.sketch_size)}</td>
+                                </tr>
+                            }
+                        }).collect::<Html>()
+                    }
+                </table>
+            }
+        };
+
+        html! {
+            <div>
+                <span class="commontext">
+                    {"Find closest strains to a genome (FASTA): "}
+                </span>
+                <input type="file" accept=".fna,.fa,.fasta,.gz" onchange={onchange_file}/>
+                <span class="commontext">
+                    {" Number of hits: "}
+                </span>
+                <input class="textbox" type="text" value={format!("{}", self.sketch_num_hits)} onchange={onchange_numhits}/>
+                { html_hits }
+            </div>
+        }
+    }
+
+
+
     ////////////////////////////////////////////////////////////
     /// Page: Search
     pub fn view_search_pane(&self, ctx: &Context<Self>) -> Html {
@@ -135,16 +205,21 @@ impl Model {
             list_colstoadd.push(html! {
                 <option selected={true}>{""}</option>
             });
-            if let Some(db_metadata) = &self.db_metadata {
-                for (colname,colmeta) in &db_metadata.columns {
-                    if colmeta.display && !self.show_columns.contains(colname){
-                        list_colstoadd.push(html! {
-                            <option value={colname.clone()}> // id={colname.clone()} 
-                                {colname.replace("_"," ")}
-                            </option>
-                        });
+            let groups = metadata.columns_by_category(|c| c.display && !self.show_columns.contains(&c.column_id));
+            for (category, list_col) in groups {
+                list_colstoadd.push(html! {
+                    <optgroup label={category}>
+                    {
+                        list_col.into_iter().map(|col| {
+                            html! {
+                                <option value={col.column_id.clone()} title={col.description.clone()}>
+                                    { col.display_name_with_unit() }
+                                </option>
+                            }
+                        }).collect::<Html>()
                     }
-                }
+                    </optgroup>
+                });
             }
 
             //// Generate HTML: all buttons below the filters
@@ -174,6 +249,7 @@ impl Model {
                                 {list_colstoadd}
                             </select>                            
                         </div>
+                        { self.view_sketch_search(&ctx) }
                     </div>
                 </div>
             }
@@ -228,6 +304,10 @@ impl Model {
                     </button>
 
                     
+                    <button class="buttonspacer" onclick={ctx.link().callback(move |_e | {MsgCore::ComputeDistanceMatrix})}>
+                        {"Distance matrix: selected"}
+                    </button>
+
                     <form target="_blank" method="get" action="https://nextstrain.org/community/vigzy77/BTracker/Bacillus-cereus-group/All-Species">
                         <input type="hidden" name="s" value={list_strains_withcomma.clone()}/>
                         <button class="buttonspacer" disabled={list_strains_withcomma.is_empty()}>
@@ -236,6 +316,9 @@ impl Model {
                     </form>
                 </div>
 
+                { self.view_distance_matrix(&ctx) }
+
+                { self.view_scatter(&ctx) }
             </div>
         }
         
@@ -1,6 +1,12 @@
 use crate::core_model::*;
 use crate::component_map::GeoMapView;
 
+use crate::appstate::AsyncData;
+use crate::download::{download_svg, download_svg_as_png, download_text_file};
+
+use my_web_app::{Binning, ChartType, NumericHistogram, OneStats, TimeSeries};
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlInputElement};
 use yew::prelude::*;
 
 impl Model {
@@ -10,31 +16,46 @@ impl Model {
 
     ////////////////////////////////////////////////////////////
     /// Page: Statistics about the database
-    pub fn view_statistics_pane(&self, _ctx: &Context<Self>) -> Html {
+    pub fn view_statistics_pane(&self, ctx: &Context<Self>) -> Html {
 
         let html_stats = if let Some(db_metadata) = &self.db_metadata {
 
-            //Make a list of all stats shown as bars
-            let mut list_stats_vec = Vec::new();
-            for onestats in &db_metadata.list_hist {
-                list_stats_vec.push(
-                    html! {
+            //Callback: Toggle between whole database and last search
+            let onchange_follow = ctx.link().callback(move |e: Event | {
+                let target: Option<EventTarget> = e.target();
+                let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).expect("wrong type");
+                MsgCore::SetStatsFollowSearch(input.checked())
+            });
+            let html_toggle = html! {
+                <div>
+                    <label>
+                        <input type="checkbox" checked={self.stats_follow_search} disabled={self.last_search.is_none()} onchange={onchange_follow}/>
+                        {" Only strains matching the current search"}
+                    </label>
+                </div>
+            };
+
+            let html_charts = if self.stats_follow_search && self.last_search.is_some() {
+                match &self.search_stats {
+                    AsyncData::Loaded(stats) => html! {
                         <div>
-                            {onestats.name.clone()}
-                            { svg_horizontal_bar_fractions(&onestats.hist) }
+                            <div>{format!("{} of {} strains match the search", stats.num_strain, db_metadata.num_strain)}</div>
+                            { self.view_stats_charts(ctx, &stats.list_hist) }
                         </div>
-                    }
-                );
-            }
+                    },
+                    AsyncData::Loading => html! { <div>{"Computing statistics..."}</div> },
+                    AsyncData::NotLoaded => html! {""},
+                }
+            } else {
+                self.view_stats_charts(ctx, &db_metadata.list_hist)
+            };
 
             html! { 
                 <p class="commontext">
-                    <div style="width:40%;margin: auto;">
-                        {"Number of genomes per country"}
-                        { GeoMapView::draw_geojson(&self.geojson, &db_metadata.hist_country) }
-                    </div>
-
-                    {list_stats_vec}
+                    {html_toggle}
+                    {html_charts}
+                    { self.view_pointmap(&ctx) }
+                    { self.view_crosstab(&ctx) }
                 </p>
             }
 
@@ -57,10 +78,77 @@ impl Model {
 
 
 
+    ////////////////////////////////////////////////////////////
+    /// Draw all charts, in the order given by the store. Each can be saved as SVG/PNG, with its counts as TSV
+    pub fn view_stats_charts(&self, ctx: &Context<Self>, list_hist: &Vec<OneStats>) -> Html {
+        list_hist.iter().enumerate().map(|(i, onestats)| {
+            let html_chart = match onestats.chart {
+                ChartType::Bar => svg_horizontal_bar_fractions(&onestats.hist),
+                ChartType::Map => {
+                    //Clicking a country searches for strains from it
+                    let column = onestats.column.clone();
+                    let on_click = ctx.link().callback(move |code: String| MsgCore::SearchByValue(column.clone(), code));
+                    GeoMapView::draw_geojson(&self.geojson, &onestats.hist, Some(on_click))
+                },
+                ChartType::Histogram => {
+                    if let Some(numeric) = &onestats.numeric {
+                        svg_numeric_histogram(numeric)
+                    } else {
+                        html! {""}
+                    }
+                },
+                ChartType::Timeseries => {
+                    if let Some(timeseries) = &onestats.timeseries {
+                        svg_timeseries(timeseries)
+                    } else {
+                        html! {""}
+                    }
+                },
+            };
+            let style = if onestats.chart == ChartType::Map { "width:40%;margin: auto;" } else { "" };
+            let figure_id = format!("statfigure_{}", i);
+            html! {
+                <div id={figure_id.clone()} style={style}>
+                    {onestats.name.clone()}
+                    { html_chart }
+                    { figure_downloads(&figure_id, &onestats.name, onestats.to_tsv()) }
+                </div>
+            }
+        }).collect::<Html>()
+    }
+
+
+
 
 }
 
 
+////////////////////////////////////////////////////////////
+/// Buttons to save a figure as SVG or PNG, and the data behind it as TSV
+fn figure_downloads(figure_id: &String, title: &String, tsv: String) -> Html {
+
+    //File name from the title of the figure
+    let basename: String = title.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    let (id, name) = (figure_id.clone(), format!("{}.svg", basename));
+    let onclick_svg = Callback::from(move |_e: MouseEvent| download_svg(&id, &name));
+    let (id, name) = (figure_id.clone(), format!("{}.png", basename));
+    let onclick_png = Callback::from(move |_e: MouseEvent| download_svg_as_png(&id, &name));
+    let name = format!("{}.tsv", basename);
+    let onclick_tsv = Callback::from(move |_e: MouseEvent| download_text_file(&name, "text/tab-separated-values", &tsv));
+
+    html! {
+        <div>
+            <button class="buttonspacer" onclick={onclick_svg}>{"SVG"}</button>
+            <button class="buttonspacer" onclick={onclick_png}>{"PNG"}</button>
+            <button class="buttonspacer" onclick={onclick_tsv}>{"TSV"}</button>
+        </div>
+    }
+}
+
+
 
 ////////////////////////////////////////////////////////////
 /// x
@@ -149,3 +237,194 @@ pub fn svg_horizontal_bar_fractions(list_entry: &DatabaseHistogram) -> Html {
 
 }
 
+
+
+////////////////////////////////////////////////////////////
+/// Short text for a bin edge or summary value
+fn format_stat_value(v: f64) -> String {
+    if v.abs() >= 10000.0 {
+        format!("{:.2e}", v)
+    } else if v.fract() == 0.0 {
+        format!("{}", v)
+    } else {
+        format!("{:.2}", v)
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Draw a binned numeric histogram with summary statistics. Bars are placed on a linear or log axis,
+/// with height given by the density so that bins of unequal width compare fairly
+pub fn svg_numeric_histogram(hist: &NumericHistogram) -> Html {
+
+    let summary = format!(
+        "n={}, min={}, max={}, mean={}, median={}",
+        hist.num_values,
+        format_stat_value(hist.min),
+        format_stat_value(hist.max),
+        format_stat_value(hist.mean),
+        format_stat_value(hist.median)
+    );
+
+    if hist.counts.is_empty() {
+        return html! { <div>{summary}</div> };
+    }
+
+    let width = 800.0;
+    let height = 100.0;
+    let margin_left = 40.0;
+
+    //Position on the x axis
+    let axis = |v: f64| if hist.binning == Binning::Log { v.max(1e-300).log10() } else { v };
+    let x_from = axis(hist.edges[0]);
+    let x_to = axis(*hist.edges.last().unwrap());
+    let x_range = if x_to > x_from { x_to - x_from } else { 1.0 };
+    let to_screen = |v: f64| margin_left + (axis(v) - x_from) / x_range * width;
+
+    //Density per bin
+    let num_bins = hist.counts.len();
+    let density: Vec<f64> = (0..num_bins).map(|i| {
+        let w = axis(hist.edges[i+1]) - axis(hist.edges[i]);
+        let w = if w > 0.0 { w } else { x_range / num_bins as f64 };
+        hist.counts[i] as f64 / w
+    }).collect();
+    let max_density = density.iter().cloned().fold(0.0, f64::max);
+    let scale_y = if max_density > 0.0 { height / max_density } else { 0.0 };
+
+    let mut outp = Vec::new();
+    for i in 0..num_bins {
+        let x1 = to_screen(hist.edges[i]);
+        let x2 = if hist.edges[i+1] > hist.edges[i] { to_screen(hist.edges[i+1]) } else { x1 + width / num_bins as f64 };
+        let h = density[i] * scale_y;
+        outp.push(html! {
+            <rect x={format!("{}", x1)} y={format!("{}", height - h)} width={format!("{}", (x2 - x1 - 1.0).max(1.0))} height={format!("{}", h)} style="fill:blue;">
+                <title>{format!("{} - {}: {}", format_stat_value(hist.edges[i]), format_stat_value(hist.edges[i+1]), hist.counts[i])}</title>
+            </rect>
+        });
+    }
+
+    //Axis labels at the ends and the middle
+    let mid = if hist.binning == Binning::Log {
+        10f64.powf((x_from + x_to) / 2.0)
+    } else {
+        (hist.edges[0] + hist.edges[hist.edges.len()-1]) / 2.0
+    };
+    for v in [hist.edges[0], mid, hist.edges[hist.edges.len()-1]].iter() {
+        outp.push(html! {
+            <text style="font-size:10.5833px;font-family:sans-serif;fill:#000;" text-anchor="middle" x={format!("{}", to_screen(*v))} y={format!("{}", height + 14.0)}>
+                {format_stat_value(*v)}
+            </text>
+        });
+    }
+
+    html! {
+        <div>
+            <svg viewBox={format!("0 0 {} {}", width + 2.0 * margin_left, height + 20.0)} xmlns="http://www.w3.org/2000/svg">
+                { outp }
+            </svg>
+            <div>{summary}</div>
+        </div>
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Colors for categorical data
+pub const CATEGORY_PALETTE: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+    "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+////////////////////////////////////////////////////////////
+/// Color for the i:th category
+pub fn category_color(i: usize) -> &'static str {
+    CATEGORY_PALETTE[i % CATEGORY_PALETTE.len()]
+}
+
+
+////////////////////////////////////////////////////////////
+/// Draw counts per year as stacked bars, with a legend if there is more than one series
+pub fn svg_timeseries(ts: &TimeSeries) -> Html {
+
+    if ts.years.is_empty() {
+        return html! { <div>{"No data"}</div> };
+    }
+
+    let width = 800.0;
+    let height = 150.0;
+    let margin_left = 40.0;
+    let bar_w = width / ts.years.len() as f64;
+
+    let totals: Vec<i32> = (0..ts.years.len()).map(|i| ts.series.iter().map(|(_, c)| c[i]).sum()).collect();
+    let max_total = *totals.iter().max().unwrap_or(&0);
+    let scale_y = if max_total > 0 { height / max_total as f64 } else { 0.0 };
+
+    let mut outp = Vec::new();
+    for (i, year) in ts.years.iter().enumerate() {
+        let x = margin_left + bar_w * i as f64;
+        let mut cur_y = height;
+        for (j, (name, counts)) in ts.series.iter().enumerate() {
+            let h = counts[i] as f64 * scale_y;
+            if counts[i] > 0 {
+                outp.push(html! {
+                    <rect x={format!("{}", x)} y={format!("{}", cur_y - h)} width={format!("{}", (bar_w - 1.0).max(1.0))} height={format!("{}", h)} style={format!("fill:{};", category_color(j))}>
+                        <title>{format!("{} {}: {}", year, name, counts[i])}</title>
+                    </rect>
+                });
+            }
+            cur_y -= h;
+        }
+    }
+
+    //Year labels; only some if there are many years
+    let label_every = (ts.years.len() / 20).max(1);
+    for (i, year) in ts.years.iter().enumerate() {
+        if i % label_every == 0 {
+            let x = margin_left + bar_w * (i as f64 + 0.5);
+            outp.push(html! {
+                <text style="font-size:10.5833px;font-family:sans-serif;fill:#000;" transform={format!("translate({},{}) rotate(45)", x, height + 8.0)}>
+                    {format!("{}", year)}
+                </text>
+            });
+        }
+    }
+    outp.push(html! {
+        <text style="font-size:10.5833px;font-family:sans-serif;fill:#000;" text-anchor="end" x={format!("{}", margin_left - 4.0)} y="10">
+            {format!("{}", max_total)}
+        </text>
+    });
+
+    //Legend below the bars, in the same SVG so that it is included when the chart is saved.
+    //Entries wrap onto new rows; their width is estimated from the length of the name
+    let mut legend_x = margin_left;
+    let mut legend_y = height + 50.0;
+    if ts.series.len() > 1 {
+        for (j, (name, _)) in ts.series.iter().enumerate() {
+            let entry_w = 30.0 + 6.0 * name.chars().count() as f64;
+            if legend_x + entry_w > width + margin_left && legend_x > margin_left {
+                legend_x = margin_left;
+                legend_y += 16.0;
+            }
+            outp.push(html! {
+                <g>
+                    <rect x={format!("{}", legend_x)} y={format!("{}", legend_y - 9.0)} width="10" height="10" style={format!("fill:{};", category_color(j))}/>
+                    <text style="font-size:10.5833px;font-family:sans-serif;fill:#000;" x={format!("{}", legend_x + 14.0)} y={format!("{}", legend_y)}>
+                        {name.clone()}
+                    </text>
+                </g>
+            });
+            legend_x += entry_w;
+        }
+    } else {
+        legend_y = height + 30.0;
+    }
+
+    html! {
+        <div>
+            <svg viewBox={format!("0 0 {} {}", width + 2.0 * margin_left, legend_y + 10.0)} xmlns="http://www.w3.org/2000/svg">
+                { outp }
+            </svg>
+        </div>
+    }
+}
@@ -182,10 +182,15 @@ impl Model {
                                     MsgCore::HideColumn(copy_colname.clone())
                                 });
 
-                                //Generate HTML for column header
-                                let pretty_txt = str::replace(txt, "_", " ");
+                                //Generate HTML for column header, using the column label if there is one
+                                let colmeta = self.db_metadata.as_ref().and_then(|m| m.columns.get(txt));
+                                let pretty_txt = match colmeta {
+                                    Some(colmeta) => colmeta.display_name_with_unit(),
+                                    None => str::replace(txt, "_", " "),
+                                };
+                                let tooltip = colmeta.map(|c| c.description.clone()).unwrap_or_default();
                                 html!{
-                                    <th key={*i} class="tableheader"> 
+                                    <th key={*i} class="tableheader" title={tooltip}> 
                                         {pretty_txt} 
                                         <button onclick={remove_onclick} class="hidecolumnbutton">{"X"}</button>
                                     </th>
@@ -3,6 +3,7 @@ pub mod treeview_model;
 pub mod treeview_camera;
 pub mod treeview_main;
 pub mod treelayout;
+pub mod treecolor;
 
 //Re-exports
 pub use treeview_camera::Camera2D;
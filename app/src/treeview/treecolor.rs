@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use my_web_app::ColumnValues;
+use yew::{html, Html};
+
+use crate::model_stats::category_color;
+use crate::treeview::treelayout::TreeLayout;
+use crate::treeview::treeview_main::parse_rgb_f64;
+
+
+/// Number of most common values given their own color; the rest share OTHER_COLOR
+const MAX_COLORS: usize = 9;
+
+/// Color of values outside the most common ones
+const OTHER_COLOR: &str = "#aaaaaa";
+
+/// Stops of the continuous scale for numeric columns, from low to high
+const NUMERIC_SCALE: [&str; 3] = ["#440154", "#21918c", "#fde725"];
+
+
+////////////////////////////////////////////////////////////
+/// What the colors mean
+#[derive(Debug, PartialEq)]
+pub enum ColorLegend {
+    /// Value and its color, most common first
+    Categories(Vec<(String, String)>, bool),
+    /// Lowest and highest value
+    Numeric(f32, f32),
+}
+
+
+////////////////////////////////////////////////////////////
+/// Color of each node given the values of a column. Tips get the color of their value; an internal node
+/// gets a color only if all its children have the same, so that monophyletic clades are colored as one
+#[derive(Debug)]
+pub struct TreeColoring {
+    pub node_color: Vec<Option<(f32,f32,f32)>>,
+    pub legend: ColorLegend,
+}
+impl TreeColoring {
+
+    ////////////////////////////////////////////////////////////
+    /// Color nodes by a column. Strains without a value are left uncolored
+    pub fn new(treedata: &TreeLayout, values: &ColumnValues, index: &HashMap<String, usize>, column: &str, numeric: bool) -> TreeColoring {
+
+        let num_nodes = treedata.names.len();
+        let list_values = values.column(column);
+        let tip_value = |id: usize| -> Option<&String> {
+            let i = index.get(&treedata.names[id])?;
+            list_values?.get(*i).filter(|v| !v.is_empty())
+        };
+
+        //Tips: color by value, and an ID of the color for comparing clades
+        let mut node_key: Vec<Option<u32>> = vec![None; num_nodes];
+        let mut node_color = vec![None; num_nodes];
+        let legend = if numeric {
+            let tip_num: Vec<Option<f32>> = (0..num_nodes)
+                .map(|id| if treedata.is_leaf(id) { tip_value(id).and_then(|v| v.parse::<f32>().ok()) } else { None })
+                .collect();
+            let min = tip_num.iter().flatten().cloned().fold(f32::MAX, f32::min);
+            let max = tip_num.iter().flatten().cloned().fold(f32::MIN, f32::max);
+            let (min, max) = if min <= max { (min, max) } else { (0.0, 0.0) };
+            for (id, v) in tip_num.iter().enumerate() {
+                if let Some(v) = v {
+                    let t = if max > min { (v - min) / (max - min) } else { 0.0 };
+                    node_key[id] = Some(v.to_bits());
+                    node_color[id] = Some(numeric_color(t));
+                }
+            }
+            ColorLegend::Numeric(min, max)
+        } else {
+            //Most common values get their own color
+            let mut counts: HashMap<&String, usize> = HashMap::new();
+            for id in 0..num_nodes {
+                if treedata.is_leaf(id) {
+                    if let Some(v) = tip_value(id) {
+                        *counts.entry(v).or_insert(0) += 1;
+                    }
+                }
+            }
+            let mut list_counts: Vec<(&String, usize)> = counts.into_iter().collect();
+            list_counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            let has_other = list_counts.len() > MAX_COLORS;
+            let map_category: HashMap<&String, usize> = list_counts.iter().take(MAX_COLORS).enumerate().map(|(i, (v, _))| (*v, i)).collect();
+
+            for id in 0..num_nodes {
+                if treedata.is_leaf(id) {
+                    if let Some(v) = tip_value(id) {
+                        let (key, color) = match map_category.get(v) {
+                            Some(i) => (*i, category_color(*i)),
+                            None => (MAX_COLORS, OTHER_COLOR),
+                        };
+                        node_key[id] = Some(key as u32);
+                        node_color[id] = Some(parse_rgb_f64(&color.to_string()));
+                    }
+                }
+            }
+            ColorLegend::Categories(
+                list_counts.iter().take(MAX_COLORS).enumerate().map(|(i, (v, _))| ((*v).clone(), category_color(i).to_string())).collect(),
+                has_other
+            )
+        };
+
+        //Internal nodes: scan bottom-up, as node IDs are in postorder
+        for id in 0..num_nodes {
+            let children = &treedata.children[id];
+            if let Some(first) = children.first() {
+                let key = node_key[*first];
+                if key.is_some() && children.iter().all(|c| node_key[*c] == key) {
+                    node_key[id] = key;
+                    node_color[id] = node_color[*first];
+                }
+            }
+        }
+
+        TreeColoring {
+            node_color,
+            legend,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Legend, as swatches for categories or a gradient for numbers
+    pub fn view_legend(&self) -> Html {
+        match &self.legend {
+            ColorLegend::Categories(list_cat, has_other) => {
+                let swatch = |name: &String, color: &str| html! {
+                    <span class="buttonspacer">
+                        <span style={format!("display:inline-block;width:10px;height:10px;background:{};", color)}></span>
+                        {format!(" {}", name)}
+                    </span>
+                };
+                html! {
+                    <div class="commontext">
+                        { list_cat.iter().map(|(name, color)| swatch(name, color)).collect::<Html>() }
+                        { if *has_other { swatch(&"Other".to_string(), OTHER_COLOR) } else { html! {""} } }
+                    </div>
+                }
+            },
+            ColorLegend::Numeric(min, max) => {
+                let stops = NUMERIC_SCALE.iter().enumerate().map(|(i, color)| html! {
+                    <stop offset={format!("{}%", 100 * i / (NUMERIC_SCALE.len() - 1))} stop-color={*color}/>
+                }).collect::<Html>();
+                html! {
+                    <div class="commontext">
+                        <svg viewBox="0 0 320 28" style="max-width:400px;">
+                            <defs>
+                                <linearGradient id="treescale">{stops}</linearGradient>
+                            </defs>
+                            <rect x="10" y="0" width="300" height="10" fill="url(#treescale)"/>
+                            <text x="10" y="24" text-anchor="start" style="font-size:9px;font-family:sans-serif;">{format!("{}", min)}</text>
+                            <text x="310" y="24" text-anchor="end" style="font-size:9px;font-family:sans-serif;">{format!("{}", max)}</text>
+                        </svg>
+                    </div>
+                }
+            },
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Color for a scaled value 0..1, interpolated between the stops of the scale
+fn numeric_color(t: f32) -> (f32,f32,f32) {
+    let pos = t.clamp(0.0, 1.0) * (NUMERIC_SCALE.len() - 1) as f32;
+    let i = (pos as usize).min(NUMERIC_SCALE.len() - 2);
+    let f = pos - i as f32;
+    let a = parse_rgb_f64(&NUMERIC_SCALE[i].to_string());
+    let b = parse_rgb_f64(&NUMERIC_SCALE[i+1].to_string());
+    (
+        a.0 + (b.0 - a.0) * f,
+        a.1 + (b.1 - a.1) * f,
+        a.2 + (b.2 - a.2) * f,
+    )
+}
+
@@ -1,19 +1,72 @@
-
 use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
 
-use phylo::prelude::*;
+use my_web_app::treelayout::TreeLayoutData;
 
 use crate::treeview::Rectangle2D;
 
+/// Angle covered by one line of an arc in the circular layout
+const ARC_STEP: f32 = PI / 180.0;
+
+/// Largest number of lines in one arc
+const MAX_ARC_SEGMENTS: usize = 64;
+
+/// Room taken by a collapsed clade, in number of tips
+const COLLAPSED_SLOTS: f32 = 3.0;
+
+
 ////////////////////////////////////////////////////////////
-/// x
-#[derive(Debug)]
-pub struct TreeLayout {
+/// How to draw the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeShape {
+    Rectangular,
+    Circular,
+    Unrooted,
+}
+impl TreeShape {
+
+    ////////////////////////////////////////////////////////////
+    /// All shapes, in the order to offer them
+    pub fn all() -> Vec<TreeShape> {
+        vec![TreeShape::Rectangular, TreeShape::Circular, TreeShape::Unrooted]
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Name to show and to use in dropdowns
+    pub fn name(&self) -> &'static str {
+        match self {
+            TreeShape::Rectangular => "Rectangular",
+            TreeShape::Circular => "Circular",
+            TreeShape::Unrooted => "Unrooted",
+        }
+    }
 
-    pub tree: SimpleRootedTree<String, f32, f32>,
+    ////////////////////////////////////////////////////////////
+    /// Parse from name
+    pub fn from_name(name: &str) -> Option<TreeShape> {
+        Self::all().into_iter().find(|s| s.name() == name)
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Should x and y be drawn at the same scale? Only the rectangular layout can be stretched
+    pub fn keep_aspect(&self) -> bool {
+        *self != TreeShape::Rectangular
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Tree structure as received from the server, and its layout in one of several shapes.
+/// Node IDs are in postorder; the root is last
+#[derive(Debug, Clone)]
+pub struct TreeLayout {
 
     pub map_name_to_id: HashMap<String, usize>,
 
+    pub names: Vec<String>,
+    pub parent: Vec<Option<usize>>,
+    pub children: Vec<Vec<usize>>,
+
     pub list_x: Vec<f32>,
     pub list_y: Vec<f32>,
 
@@ -26,155 +79,391 @@ pub struct TreeLayout {
     pub vec_owner: Vec<usize>,
     pub vec_vertex: Vec<f32>,
     pub gl_num_lines: u32,
+
+    /// Length of the branch to the parent of each node
+    pub branch_length: Vec<f32>,
+
+    pub shape: TreeShape,
+    /// Ignore branch lengths
+    pub cladogram: bool,
+
+    /// Clades drawn as a triangle
+    pub collapsed: HashSet<usize>,
+    /// Nodes inside a collapsed clade. These are placed at the far end of its triangle
+    pub hidden: Vec<bool>,
 }
 impl TreeLayout {
 
 
     ////////////////////////////////////////////////////////////
-    /// x
-    pub fn new(newick_str: &str) -> TreeLayout {
-
-//        let newick_str = "((A:0.1,B:0.2)F:0.6,(C:0.3,D:0.4)E:0.5)G;";
-//        let newick_str = include_str!("parsnp2_noC_snps.fna.treefile"); 
-//        let newick_str = include_str!("All-Species_rooted.nwk"); 
-
-        log::debug!("reading phylo tree; how can it be so slow??");
-        let tree: SimpleRootedTree<String, f32, f32> = PhyloTree::from_newick(newick_str.as_bytes()).expect("Could not parse tree");
+    /// Set up from a layout received from the server. Its rectangular layout is used as is;
+    /// the tree is only laid out here for other shapes, collapsed clades and subtrees
+    pub fn from_data(data: TreeLayoutData) -> TreeLayout {
+        let parent: Vec<Option<usize>> = data.parent.iter().map(|p| if *p >= 0 { Some(*p as usize) } else { None }).collect();
+        let branch_length = parent.iter().enumerate()
+            .map(|(id, p)| p.map(|p| data.x[id] - data.x[p]).unwrap_or(0.0))
+            .collect();
+        let mut lay = Self::from_structure(parent, data.names, branch_length);
+        lay.list_x = data.x;
+        lay.list_y = data.y;
+        lay.vec_vertex = data.vertex;
+        lay.vec_owner = data.owner.iter().map(|o| *o as usize).collect();
+        lay.gl_num_lines = lay.vec_owner.len() as u32;
+        lay.update_extent();
+        lay
+    }
 
-        let root = tree.get_root_id();
 
-        //log::debug!("Num nodes: {}",tree.num_nodes());
+    ////////////////////////////////////////////////////////////
+    /// Set up from the tree structure, not yet laid out. Nodes must be in postorder
+    fn from_structure(parent: Vec<Option<usize>>, names: Vec<String>, branch_length: Vec<f32>) -> TreeLayout {
 
-        //Create default set of y-positions (all to be overwritten)
-        let mut list_y = Vec::with_capacity(tree.num_nodes());
-        for _i in 0..tree.num_nodes() {
-            list_y.push(0.0);
-        }
+        let num_nodes = parent.len();
 
         //Store name of all nodes
         let mut map_name_to_id:HashMap<String, usize> = HashMap::new();
-        for node in tree.get_nodes() {
-            let name = node.get_taxa();
-            if let Some(name) = name {
-                map_name_to_id.insert(name.clone(), node.get_id());
-            } 
+        for (id, name) in names.iter().enumerate() {
+            if !name.is_empty() {
+                map_name_to_id.insert(name.clone(), id);
+            }
         }
 
-        //Figure out y-position of all entries
-        let mut next_y=0.0;
-        for node_id in tree.postord_ids(root) {
-            if tree.is_leaf(node_id) {
-                //Set y-coordinate to new position
-                let this_y = list_y.get_mut(node_id).expect("foo");
-                *this_y = next_y;
-                next_y += 1.0;
-            } else {
-                //Set y-coordinate to average of children
-                let mut sum_y = 0.0;
-                let mut num_children = 0;
-                
-                for child_id in tree.get_node_children_ids(node_id) {
-                    sum_y += list_y.get(child_id).expect("Could not get child");
-                    num_children += 1;
-                }
-                let this_y = list_y.get_mut(node_id).expect("foo");
-                *this_y = sum_y / (num_children as f32);
+        //Tree structure
+        let mut children = vec![Vec::new(); num_nodes];
+        for (id, p) in parent.iter().enumerate() {
+            if let Some(p) = p {
+                children[*p].push(id);
             }
         }
-        //log::debug!("final y {}", next_y);
 
+        TreeLayout {
+            map_name_to_id,
 
+            names,
+            parent,
+            children,
 
-        //Figure out x-position of all entries        
-        let list_x = TreeLayout::calc_x(&tree);
+            list_x: Vec::new(),
+            list_y: Vec::new(),
+            max_x: 0.0,
+            min_x: 0.0,
+            max_y: 0.0,
+            min_y: 0.0,
 
-        //log::debug!("x: {:?}", list_x);
-        //log::debug!("y: {:?}", list_y);
+            gl_num_lines: 0,
+            vec_owner: Vec::new(),
+            vec_vertex: Vec::new(),
 
-        //Figure out extent of diagram, for camera
-        let max_y=next_y;
-        let min_x=0.0;
-        let min_y=0.0;
+            branch_length,
+            shape: TreeShape::Rectangular,
+            cladogram: false,
 
-        let mut max_x=0.0;
-        for v in &list_x {
-            if *v > max_x {
-                max_x=*v;
-            }
+            collapsed: HashSet::new(),
+            hidden: vec![false; num_nodes],
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Copy of the tree laid out in another shape
+    pub fn with_layout(&self, shape: TreeShape, cladogram: bool) -> TreeLayout {
+        let mut lay = self.clone();
+        lay.set_layout(shape, cladogram);
+        lay
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Copy of the tree with a clade collapsed, or expanded if it already was
+    pub fn with_collapse_toggled(&self, id: usize) -> TreeLayout {
+        let mut lay = self.clone();
+        if !lay.collapsed.remove(&id) {
+            lay.collapsed.insert(id);
         }
+        lay.set_layout(self.shape, self.cladogram);
+        lay
+    }
 
-        //Lines, set up coordinates from-to. Too few vertices to make it worth sharing data
-        let mut vec_owner: Vec<usize> = Vec::new();
-        let mut vec_vertex: Vec<f32> = Vec::new();
-        vec_vertex.reserve(tree.num_nodes()*2);
-        let mut gl_num_lines=0;        
-        for node_id in tree.postord_ids(root) {
 
-            let node_x = *list_x.get(node_id).unwrap();
-            //let node_y = *list_y.get(node_id).unwrap();
+    ////////////////////////////////////////////////////////////
+    /// The clade of a node as a tree of its own, in the same shape. Nodes in a subtree
+    /// are consecutive in postorder, starting with its first tip
+    pub fn subtree(&self, id: usize) -> TreeLayout {
+        let mut first = id;
+        while let Some(c) = self.children[first].first() {
+            first = *c;
+        }
+        let parent = (first..=id).map(|i| if i == id { None } else { self.parent[i].map(|p| p - first) }).collect();
+        let names = self.names[first..=id].to_vec();
+        let mut branch_length = self.branch_length[first..=id].to_vec();
+        branch_length[id - first] = 0.0;
+
+        let mut lay = Self::from_structure(parent, names, branch_length);
+        lay.set_layout(self.shape, self.cladogram);
+        lay
+    }
 
-            let mut child_min_y = f32::MAX;
-            let mut child_max_y = f32::MIN;
-            let mut has_children= false;
 
-            for child_id in tree.get_node_children_ids(node_id) {
+    ////////////////////////////////////////////////////////////
+    /// Compute positions and lines for a shape. With cladogram, all tips are at the same distance from the root
+    pub fn set_layout(&mut self, shape: TreeShape, cladogram: bool) {
+        let num_nodes = self.parent.len();
+
+        //Nodes inside collapsed clades are not drawn. Parents come before children when going backwards
+        let mut hidden = vec![false; num_nodes];
+        for id in (0..num_nodes).rev() {
+            if let Some(p) = self.parent[id] {
+                hidden[id] = hidden[p] || self.collapsed.contains(&p);
+            }
+        }
 
-                let child_x = *list_x.get(child_id).unwrap();
-                let child_y = *list_y.get(child_id).unwrap();
-                
-                //Could we look at first and last instead? todo
-                if child_max_y < child_y {
-                    child_max_y = child_y;
+        //Nodes drawn as ends: tips, and collapsed clades
+        let is_end: Vec<bool> = (0..num_nodes).map(|id| self.is_leaf(id) || self.collapsed.contains(&id)).collect();
+
+        //Distance from the root
+        let mut depth = vec![0.0; num_nodes];
+        if cladogram {
+            let mut height = vec![0.0f32; num_nodes];
+            for id in 0..num_nodes {
+                if !is_end[id] {
+                    height[id] = self.children[id].iter().map(|c| height[*c]).fold(0.0, f32::max) + 1.0;
                 }
-                if child_min_y > child_y {
-                    child_min_y = child_y;
+            }
+            let max_height = height[self.root()];
+            for id in 0..num_nodes {
+                depth[id] = max_height - height[id];
+            }
+        } else {
+            for id in (0..num_nodes).rev() {
+                if let Some(p) = self.parent[id] {
+                    depth[id] = depth[p] + self.branch_length[id];
                 }
+            }
+        }
 
-                //log::debug!("parent node {}", node_id);
-                vec_vertex.push(node_x);
-                vec_vertex.push(child_y);
-                vec_vertex.push(child_x);
-                vec_vertex.push(child_y);
-                vec_owner.push(child_id);
-                //log::debug!("pvertex {:?}", vec_vertex);
-
-                gl_num_lines += 1;   
+        //Distance from the root to the far end of a collapsed clade
+        let mut far = depth.clone();
+        for id in 0..num_nodes {
+            if cladogram {
+                far[id] = depth[id] + 1.0;
+            } else {
+                for c in &self.children[id] {
+                    far[id] = far[id].max(far[*c]);
+                }
+            }
+        }
 
-                has_children = true;
+        //Position along the ends, and room taken by each node. Parents are at the average of their children
+        let mut along = vec![0.0; num_nodes];
+        let mut slots = vec![0.0f32; num_nodes];
+        let mut next_along = 0.0;
+        for id in 0..num_nodes {
+            if hidden[id] {
+                continue;
+            }
+            if is_end[id] {
+                slots[id] = if self.is_leaf(id) { 1.0 } else { COLLAPSED_SLOTS };
+                along[id] = next_along + (slots[id] - 1.0) / 2.0;
+                next_along += slots[id];
+            } else {
+                let children = &self.children[id];
+                along[id] = children.iter().map(|c| along[*c]).sum::<f32>() / (children.len() as f32);
+                slots[id] = children.iter().map(|c| slots[*c]).sum();
             }
+        }
+        let total_slots = next_along.max(1.0);
+        let half_triangle = (COLLAPSED_SLOTS - 1.0) / 2.0;
+
+        let mut x = vec![0.0f32; num_nodes];
+        let mut y = vec![0.0f32; num_nodes];
+        //Middle of the far end of each collapsed clade
+        let mut end_x = vec![0.0f32; num_nodes];
+        let mut end_y = vec![0.0f32; num_nodes];
+        self.vec_vertex.clear();
+        self.vec_owner.clear();
+        match shape {
+            TreeShape::Rectangular | TreeShape::Circular => {
+                //Both are drawn from distance and position along; the circular layout wraps position along into an angle
+                let circular = shape == TreeShape::Circular;
+                let to_xy = |d: f32, a: f32| -> (f32, f32) {
+                    if circular {
+                        let angle = a / total_slots * 2.0 * PI;
+                        (d * angle.cos(), d * angle.sin())
+                    } else {
+                        (d, a)
+                    }
+                };
+                let add_line = |vertex: &mut Vec<f32>, owner: &mut Vec<usize>, p1: (f32, f32), p2: (f32, f32), id: usize| {
+                    vertex.extend_from_slice(&[p1.0, p1.1, p2.0, p2.1]);
+                    owner.push(id);
+                };
+
+                for id in 0..num_nodes {
+                    if hidden[id] {
+                        continue;
+                    }
+                    (x[id], y[id]) = to_xy(depth[id], along[id]);
+                    if self.collapsed.contains(&id) {
+                        //Triangle from the node to the far end of the clade
+                        let p0 = (x[id], y[id]);
+                        let p1 = to_xy(far[id], along[id] - half_triangle);
+                        let p2 = to_xy(far[id], along[id] + half_triangle);
+                        add_line(&mut self.vec_vertex, &mut self.vec_owner, p0, p1, id);
+                        add_line(&mut self.vec_vertex, &mut self.vec_owner, p0, p2, id);
+                        add_line(&mut self.vec_vertex, &mut self.vec_owner, p1, p2, id);
+                        (end_x[id], end_y[id]) = to_xy(far[id], along[id]);
+                        continue;
+                    }
+                    let children = &self.children[id];
+                    if children.is_empty() {
+                        continue;
+                    }
+                    //Line out to each child
+                    for c in children {
+                        add_line(&mut self.vec_vertex, &mut self.vec_owner, to_xy(depth[id], along[*c]), to_xy(depth[*c], along[*c]), *c);
+                    }
+                    //Line spanning the children; an arc, as a number of straight lines, if circular
+                    let min_a = children.iter().map(|c| along[*c]).fold(f32::MAX, f32::min);
+                    let max_a = children.iter().map(|c| along[*c]).fold(f32::MIN, f32::max);
+                    let num_seg = if circular {
+                        (((max_a - min_a) / total_slots * 2.0 * PI / ARC_STEP).ceil() as usize).clamp(1, MAX_ARC_SEGMENTS)
+                    } else {
+                        1
+                    };
+                    for i in 0..num_seg {
+                        let a1 = min_a + (max_a - min_a) * (i as f32) / (num_seg as f32);
+                        let a2 = min_a + (max_a - min_a) * ((i + 1) as f32) / (num_seg as f32);
+                        add_line(&mut self.vec_vertex, &mut self.vec_owner, to_xy(depth[id], a1), to_xy(depth[id], a2), id);
+                    }
+                }
+            },
+            TreeShape::Unrooted => {
+                //Equal-angle: each node gets a wedge in proportion to its room, shared among its children
+                let mut wedge_start = vec![0.0f32; num_nodes];
+                let mut wedge = vec![2.0 * PI; num_nodes];
+                for id in (0..num_nodes).rev() {
+                    if hidden[id] {
+                        continue;
+                    }
+                    if self.collapsed.contains(&id) {
+                        //Triangle spanning the wedge, but not so wide that it looks like a fan
+                        let mid = wedge_start[id] + wedge[id] / 2.0;
+                        let half = (wedge[id] / 2.0).min(PI / 6.0);
+                        let len = far[id] - depth[id];
+                        let p1 = (x[id] + len * (mid - half).cos(), y[id] + len * (mid - half).sin());
+                        let p2 = (x[id] + len * (mid + half).cos(), y[id] + len * (mid + half).sin());
+                        self.vec_vertex.extend_from_slice(&[x[id], y[id], p1.0, p1.1]);
+                        self.vec_vertex.extend_from_slice(&[x[id], y[id], p2.0, p2.1]);
+                        self.vec_vertex.extend_from_slice(&[p1.0, p1.1, p2.0, p2.1]);
+                        self.vec_owner.extend_from_slice(&[id, id, id]);
+                        end_x[id] = x[id] + len * mid.cos();
+                        end_y[id] = y[id] + len * mid.sin();
+                        continue;
+                    }
+                    let mut start = wedge_start[id];
+                    for c in &self.children[id] {
+                        let a = start + PI * slots[*c] / total_slots;
+                        let len = if cladogram { 1.0 } else { self.branch_length[*c] };
+                        wedge_start[*c] = start;
+                        wedge[*c] = 2.0 * PI * slots[*c] / total_slots;
+                        x[*c] = x[id] + len * a.cos();
+                        y[*c] = y[id] + len * a.sin();
+                        self.vec_vertex.extend_from_slice(&[x[id], y[id], x[*c], y[*c]]);
+                        self.vec_owner.push(*c);
+                        start += wedge[*c];
+                    }
+                }
+            },
+        }
 
-            //Vertical line at parent
-            if has_children {
-                vec_vertex.push(node_x);
-                vec_vertex.push(child_min_y);
-                vec_vertex.push(node_x);
-                vec_vertex.push(child_max_y);
-                vec_owner.push(node_id);
-                gl_num_lines += 1;   
+        //Hidden nodes are placed at the end of the triangle of their clade, so that they can still be selected
+        for id in (0..num_nodes).rev() {
+            if let Some(p) = self.parent[id] {
+                if self.collapsed.contains(&p) && !hidden[p] {
+                    (x[id], y[id]) = (end_x[p], end_y[p]);
+                } else if hidden[id] {
+                    (x[id], y[id]) = (x[p], y[p]);
+                }
             }
+        }
+        self.list_x = x;
+        self.list_y = y;
+        self.hidden = hidden;
+        self.gl_num_lines = self.vec_owner.len() as u32;
+        self.shape = shape;
+        self.cladogram = cladogram;
+        self.update_extent();
+    }
 
 
+    ////////////////////////////////////////////////////////////
+    /// Figure out extent of diagram, for camera, from nodes and lines. Leave room below the last tip of a rectangular tree
+    fn update_extent(&mut self) {
+        let all_x = self.list_x.iter().chain(self.vec_vertex.iter().step_by(2));
+        let all_y = self.list_y.iter().chain(self.vec_vertex.iter().skip(1).step_by(2));
+        self.min_x = all_x.clone().cloned().fold(0.0, f32::min);
+        self.max_x = all_x.cloned().fold(0.0, f32::max);
+        self.min_y = all_y.clone().cloned().fold(0.0, f32::min);
+        self.max_y = all_y.cloned().fold(0.0, f32::max);
+        if self.shape == TreeShape::Rectangular {
+            self.max_y += 1.0;
         }
-        //log::debug!("num lines {}", gl_num_lines);
-        //log::debug!("vertex {:?}", vec_vertex);
+    }
 
 
-        TreeLayout {
-            tree,
-            map_name_to_id,
+    ////////////////////////////////////////////////////////////
+    /// ID of the root
+    pub fn root(&self) -> usize {
+        self.parent.len() - 1
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Is the node a tip?
+    pub fn is_leaf(&self, id: usize) -> bool {
+        self.children[id].is_empty()
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// All tips in the subtree of a node, including the node itself if a tip
+    pub fn tips_under(&self, id: usize) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if self.is_leaf(id) {
+                out.push(id);
+            } else {
+                stack.extend(self.children[id].iter());
+            }
+        }
+        out
+    }
 
-            list_x,
-            list_y,
-            max_x,
-            min_x,
-            max_y,
-            min_y,
 
-            vec_owner,
-            vec_vertex,
-            gl_num_lines,
+    ////////////////////////////////////////////////////////////
+    /// Find the line closest to a position, and return the node it belongs to.
+    /// Distances are measured after scaling each axis, so that they can be given in screen units
+    pub fn closest_line(&self, wx: f32, wy: f32, scale_x: f32, scale_y: f32, max_dist: f32) -> Option<usize> {
+        let mut best = None;
+        let mut best_dist = max_dist;
+        for (i, owner) in self.vec_owner.iter().enumerate() {
+            let v = &self.vec_vertex[i*4..i*4+4];
+            let (x1, y1) = ((v[0] - wx) * scale_x, (v[1] - wy) * scale_y);
+            let (x2, y2) = ((v[2] - wx) * scale_x, (v[3] - wy) * scale_y);
+
+            //Distance from origin to the segment
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let len2 = dx*dx + dy*dy;
+            let t = if len2 > 0.0 { (-(x1*dx + y1*dy) / len2).clamp(0.0, 1.0) } else { 0.0 };
+            let (px, py) = (x1 + t*dx, y1 + t*dy);
+            let dist = (px*px + py*py).sqrt();
+            if dist < best_dist {
+                best_dist = dist;
+                best = Some(*owner);
+            }
         }
-        
+        best
     }
 
 
@@ -202,22 +491,10 @@ impl TreeLayout {
             list_sel.insert(*v);
         }
 
-        //Scan bottom-up to fill in common ancestors
-        let root = self.tree.get_root_id();
-        for node_id in self.tree.postord_ids(root) {
-
-            //Check if all children are selected
-            let mut all_children_in_sel = true;
-            let mut num_children=0;
-            for child_id in self.tree.get_node_children_ids(node_id) {                
-                if !list_sel.contains(&child_id) {
-                    all_children_in_sel = false;
-                    break;
-                }
-                num_children += 1;
-            }
-
-            if num_children>0 && all_children_in_sel {
+        //Scan bottom-up to fill in common ancestors. Node IDs are in postorder
+        for node_id in 0..self.parent.len() {
+            let children = &self.children[node_id];
+            if !children.is_empty() && children.iter().all(|c| list_sel.contains(c)) {
                 list_sel.insert(node_id);
             }
         }
@@ -229,6 +506,28 @@ impl TreeLayout {
 
 
 
+    ////////////////////////////////////////////////////////////
+    /// Rectangle around some nodes, if any
+    pub fn get_bounding_rect_of(&self, list_ids: &Vec<usize>) -> Option<Rectangle2D> {
+        if list_ids.is_empty() {
+            return None;
+        }
+        let mut rect = Rectangle2D {
+            x1: f32::MAX,
+            x2: f32::MIN,
+            y1: f32::MAX,
+            y2: f32::MIN,
+        };
+        for id in list_ids {
+            rect.x1 = rect.x1.min(self.list_x[*id]);
+            rect.x2 = rect.x2.max(self.list_x[*id]);
+            rect.y1 = rect.y1.min(self.list_y[*id]);
+            rect.y2 = rect.y2.max(self.list_y[*id]);
+        }
+        Some(rect)
+    }
+
+
     ////////////////////////////////////////////////////////////
     /// x
     pub fn get_bounding_rect(&self) -> Rectangle2D {
@@ -241,45 +540,4 @@ impl TreeLayout {
         }
     }
 
-
-
-    ////////////////////////////////////////////////////////////
-    /// x
-    fn calc_x<T,W,Z>(tree: &SimpleRootedTree<T,W,Z>) -> Vec<W> where 
-            T: NodeTaxa,
-            W: EdgeWeight,
-            Z: NodeWeight {
-
-        let mut list_x = Vec::with_capacity(tree.num_nodes());
-        for _i in 0..tree.num_nodes() {
-            list_x.push(W::zero());
-        }
-
-        let cur_node = tree.get_root_id();
-        let next_w = W::zero();
-        TreeLayout::calc_x_recursive(tree, &mut list_x, cur_node, next_w);
-
-        list_x
-    }
-
-
-        
-    ////////////////////////////////////////////////////////////
-    /// x
-    fn calc_x_recursive<T,W,Z>(tree: &SimpleRootedTree<T,W,Z>, list_x: &mut Vec<W>, cur_node: usize, cur_x: W) where 
-            T: NodeTaxa,
-            W: EdgeWeight,
-            Z: NodeWeight {
-
-        for child_id in tree.get_node_children_ids(cur_node) {
-                let w= tree.get_edge_weight(cur_node, child_id).expect("no edge weight");
-                let next_w = cur_x + w;
-                let this_x = list_x.get_mut(child_id).expect("foo");
-                *this_x = next_w;
-                TreeLayout::calc_x_recursive(tree, list_x, child_id, next_w);
-            }
-
-        }
-
-
-}
\ No newline at end of file
+}
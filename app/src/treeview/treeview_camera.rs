@@ -2,7 +2,7 @@
 
 ////////////////////////////////////////////////////////////
 /// A camera for 2D scenes
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Camera2D {
     pub x: f32,
     pub y: f32,
@@ -65,6 +65,29 @@ impl Camera2D {
     }
 
 
+    ////////////////////////////////////////////////////////////
+    /// Adjust camera to fit all points, with the same scale in x and y. The aspect ratio is width/height of the canvas
+    pub fn fit_keep_aspect(&mut self, umap: &Rectangle2D, aspect: f32) {
+        self.fit_reduction(umap);
+        self.zoom_x = self.zoom_x.min(self.zoom_y / aspect);
+        self.zoom_y = self.zoom_x * aspect;
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Camera part of the way, 0..1, from one camera to another. Zoom is interpolated on a log scale so that
+    /// zooming appears to happen at an even pace
+    pub fn interpolate(from: &Camera2D, to: &Camera2D, t: f32) -> Camera2D {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Camera2D {
+            x: lerp(from.x, to.x),
+            y: lerp(from.y, to.y),
+            zoom_x: lerp(from.zoom_x.ln(), to.zoom_x.ln()).exp(),
+            zoom_y: lerp(from.zoom_y.ln(), to.zoom_y.ln()).exp(),
+        }
+    }
+
+
     ////////////////////////////////////////////////////////////
     /// Zoom in and out around a given position
     /// 
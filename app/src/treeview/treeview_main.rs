@@ -1,16 +1,20 @@
 
-use my_web_app::TableData;
+use std::collections::{HashMap, HashSet};
+
+use my_web_app::{ColumnValues, TableData};
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use web_sys::window;
-use web_sys::{DomRect, EventTarget, HtmlCanvasElement, WebGlRenderingContext as GL};
+use web_sys::{DomRect, EventTarget, HtmlCanvasElement, WebGlBuffer, WebGlProgram, WebGlRenderingContext as GL};
 use yew::{html, Callback, Component, Context, Html, MouseEvent, NodeRef, WheelEvent};
 use yew::Properties;
 
 use crate::appstate::AsyncData;
 use crate::core_model::MsgCore;
-use crate::treeview::Camera2D;
+use crate::treeview::{Camera2D, Rectangle2D};
 use crate::resize::ComponentSize;
 use crate::treeview::treelayout::TreeLayout;
+use crate::treeview::treecolor::TreeColoring;
 
 
 
@@ -26,17 +30,50 @@ type Vec3 = (f32,f32,f32);
 
 
 
+/// Largest distance to a line, in pixels, for a click to hit it
+const CLICK_DISTANCE: f32 = 5.0;
+
+/// Tip labels are shown once there are this many pixels of canvas height per tip in view
+const LABEL_MIN_SPACING: f32 = 10.0;
+
+/// Largest number of tip labels drawn at once
+const MAX_LABELS: usize = 500;
+
+/// Time to move the camera to found tips, in ms
+const ANIMATION_TIME: f64 = 600.0;
+
+/// Smallest part of the tree to zoom in on when finding tips, as number of tips in the rectangular layout,
+/// and as part of the size of the whole tree otherwise
+const FOCUS_MIN_TIPS: f32 = 30.0;
+const FOCUS_MIN_FRACTION: f32 = 0.02;
+
+
 ////////////////////////////////////////////////////////////
 /// Message sent to the event system for updating the page
 #[derive(Debug)]
 pub enum MsgTree {
+    MouseDown(f32,f32, bool),
     MouseMove(f32,f32, bool),
-    MouseClick,
+    MouseUp(f32,f32),
+    MouseLeave,
     MouseWheel(f32),
+    AnimationFrame(f64),
+    ContextMenu(f32,f32),
+    CloseMenu,
     Propagate(MsgCore),
 }
 
 
+////////////////////////////////////////////////////////////
+/// Camera moving from one place to another. The start time is set by the first frame
+#[derive(Debug)]
+struct CameraAnimation {
+    from: Camera2D,
+    to: Camera2D,
+    start: Option<f64>,
+}
+
+
 ////////////////////////////////////////////////////////////
 /// Properties for ReductionView
 #[derive(Properties, PartialEq)]
@@ -45,16 +82,58 @@ pub struct Props {
     pub last_component_size: ComponentSize,
     pub treedata: AsyncData<TreeLayout>,
     pub tabledata: AsyncData<TableData>,
+    pub selected_strains: HashSet<String>,
+    /// Changes whenever selected_strains does
+    pub selection_version: u64,
+    /// Metadata of all strains, for labels and tooltips
+    pub tree_metadata: AsyncData<ColumnValues>,
+    /// Column to label tips by; the strain ID if empty
+    pub label_column: String,
+    /// Columns shown in tooltips, as column ID and name to show
+    pub tooltip_columns: Vec<(String, String)>,
+    /// Column to color branches by; search hits are highlighted instead if empty
+    pub color_column: String,
+    /// Color by a continuous scale rather than by category
+    pub color_numeric: bool,
+    /// Strains to zoom to and highlight. A new list is given each time the camera should move
+    pub focus_strains: AsyncData<Vec<String>>,
+    /// Column to name collapsed clades by their most common value; not named if empty
+    pub species_column: String,
 }
 
 
+/// What the line colors depend on: search result, selection, metadata and column to color by
+type ColorKey = (AsyncData<TableData>, u64, AsyncData<ColumnValues>, String);
+
+
 ////////////////////////////////////////////////////////////
 /// random note: Wrap gl in Rc (Arc for multi-threaded) so it can be injected into the render-loop closure.
 pub struct TreeView {
     node_ref: NodeRef,
     last_pos: (f32,f32),
+    down_pos: Option<(f32,f32)>,
+    /// Corners of the selection rectangle being drawn, in world coordinates
+    select_rect: Option<((f32,f32),(f32,f32))>,
     camera: Camera2D,
     last_treedata: AsyncData<TreeLayout>,
+    gl: Option<TreeGl>,
+    /// Node under the mouse
+    hover: Option<usize>,
+    /// Row of each strain in tree_metadata
+    meta_index: HashMap<String, usize>,
+    meta_for: AsyncData<ColumnValues>,
+    /// Colors by metadata, and what they were computed for
+    coloring: Option<TreeColoring>,
+    coloring_for: Option<(AsyncData<TreeLayout>, AsyncData<ColumnValues>, String, bool)>,
+    /// Tips to highlight, and the list they were found from
+    focus_ids: Vec<usize>,
+    focus_for: AsyncData<Vec<String>>,
+    animation: Option<CameraAnimation>,
+    /// Node a menu is open for, and where the menu is, in camera coordinates
+    menu: Option<(usize, (f32,f32))>,
+    /// Labels of collapsed clades, and what they were computed for
+    collapsed_labels: HashMap<usize, String>,
+    collapsed_labels_for: Option<(AsyncData<TreeLayout>, AsyncData<ColumnValues>, String)>,
 }
 
 impl Component for TreeView {
@@ -63,13 +142,43 @@ impl Component for TreeView {
 
     ////////////////////////////////////////////////////////////
     /// Create this component
-    fn create(_ctx: &Context<Self>) -> Self {
-        Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut view = Self {
             node_ref: NodeRef::default(),
             last_pos: (0.0,0.0),
+            down_pos: None,
+            select_rect: None,
             camera: Camera2D::new(),
-            last_treedata: AsyncData::NotLoaded
-        }
+            last_treedata: AsyncData::NotLoaded,
+            gl: None,
+            hover: None,
+            meta_index: HashMap::new(),
+            meta_for: AsyncData::NotLoaded,
+            coloring: None,
+            coloring_for: None,
+            focus_ids: Vec::new(),
+            focus_for: AsyncData::NotLoaded,
+            animation: None,
+            menu: None,
+            collapsed_labels: HashMap::new(),
+            collapsed_labels_for: None,
+        };
+        view.update_meta_index(ctx);
+        view.update_coloring(ctx);
+        view.update_focus(ctx);
+        view.update_collapsed_labels(ctx);
+        view
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Properties changed
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.update_meta_index(ctx);
+        self.update_coloring(ctx);
+        self.update_focus(ctx);
+        self.update_collapsed_labels(ctx);
+        true
     }
 
 
@@ -86,6 +195,20 @@ impl Component for TreeView {
                 false
             },
 
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse button pressed. With shift, a selection rectangle is started
+            MsgTree::MouseDown(x,y, shift) => {
+                self.animation = None;
+                self.menu = None;
+                self.last_pos = (x,y);
+                self.down_pos = Some((x,y));
+                if shift {
+                    let corner = self.camera.cam2world(x,y);
+                    self.select_rect = Some((corner, corner));
+                }
+                false
+            },
+
             ////////////////////////////////////////////////////////////
             // Message: Mouse has moved
             MsgTree::MouseMove(x,y, press_left) => {
@@ -93,6 +216,17 @@ impl Component for TreeView {
                 self.last_pos = (x,y);
                 //  log::debug!(".. {:?}", last_pos);
 
+                //Handle selection rectangle. If the button is no longer pressed, it was released elsewhere; drop the rectangle
+                if !press_left && self.select_rect.is_some() {
+                    self.select_rect = None;
+                    self.down_pos = None;
+                    return true;
+                }
+                if let Some((_, corner)) = &mut self.select_rect {
+                    *corner = self.camera.cam2world(x,y);
+                    return true;
+                }
+
                 //Handle panning
                 if press_left {
                     let dx = x - last_pos.0;
@@ -100,15 +234,39 @@ impl Component for TreeView {
                     //log::debug!("dd {:?}", (dx,dy));
                     self.camera.x -= (dx as f32) / self.camera.zoom_x;
                     self.camera.y -= (dy as f32) / self.camera.zoom_y;
+                    self.hover = None;
                     return true;
                 }
 
-                false
+                //Find what is under the mouse, for the tooltip
+                let hover = if let AsyncData::Loaded(treedata) = &ctx.props().treedata {
+                    let (wx, wy) = self.camera.cam2world(x,y);
+                    let (scale_x, scale_y) = self.world2pixel_scale();
+                    treedata.closest_line(wx, wy, scale_x, scale_y, CLICK_DISTANCE)
+                } else {
+                    None
+                };
+                //Redraw to move the tooltip along
+                let changed = hover != self.hover || hover.is_some();
+                self.hover = hover;
+                changed
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse has left the tree
+            MsgTree::MouseLeave => {
+                //The button may be released outside the canvas, so any ongoing drag or selection ends here
+                let changed = self.hover.is_some() || self.select_rect.is_some();
+                self.hover = None;
+                self.select_rect = None;
+                self.down_pos = None;
+                changed
             },
 
             ////////////////////////////////////////////////////////////
             // Message: Mouse wheel rotated
             MsgTree::MouseWheel(dy) => {
+                self.animation = None;
                 let (cx,cy) = self.last_pos;
                 let (wx, wy) = self.camera.cam2world(cx, cy);
                 let scale = (10.0f32).powf(dy / 100.0);
@@ -117,8 +275,92 @@ impl Component for TreeView {
             },
 
             ////////////////////////////////////////////////////////////
-            // Message: Mouse has clicked
-            MsgTree::MouseClick => {
+            // Message: Right-click. Opens a menu for the clade under the mouse
+            MsgTree::ContextMenu(x,y) => {
+                self.menu = if let AsyncData::Loaded(treedata) = &ctx.props().treedata {
+                    let (wx, wy) = self.camera.cam2world(x,y);
+                    let (scale_x, scale_y) = self.world2pixel_scale();
+                    treedata.closest_line(wx, wy, scale_x, scale_y, CLICK_DISTANCE)
+                        .filter(|id| !treedata.is_leaf(*id))
+                        .map(|id| (id, (x,y)))
+                } else {
+                    None
+                };
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Close the clade menu
+            MsgTree::CloseMenu => {
+                self.menu = None;
+                true
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Time to draw the next frame of a camera animation
+            MsgTree::AnimationFrame(time) => {
+                if let Some(anim) = &mut self.animation {
+                    let start = *anim.start.get_or_insert(time);
+                    let t = ((time - start) / ANIMATION_TIME).clamp(0.0, 1.0) as f32;
+                    //Ease in and out
+                    let t = t * t * (3.0 - 2.0 * t);
+                    self.camera = Camera2D::interpolate(&anim.from, &anim.to, t);
+                    if t < 1.0 {
+                        Self::request_animation_frame(ctx);
+                    } else {
+                        self.animation = None;
+                    }
+                    true
+                } else {
+                    false
+                }
+            },
+
+            ////////////////////////////////////////////////////////////
+            // Message: Mouse button released. Either ends a selection rectangle, or is a click if the mouse did not move
+            MsgTree::MouseUp(x,y) => {
+                let down_pos = self.down_pos.take();
+                let treedata = if let AsyncData::Loaded(treedata) = &ctx.props().treedata {
+                    treedata
+                } else {
+                    return false;
+                };
+
+                //Select all tips inside the rectangle
+                if let Some((c1, c2)) = self.select_rect.take() {
+                    let (x1, x2) = (c1.0.min(c2.0), c1.0.max(c2.0));
+                    let (y1, y2) = (c1.1.min(c2.1), c1.1.max(c2.1));
+                    let list_sel: Vec<String> = (0..treedata.names.len())
+                        .filter(|id| treedata.is_leaf(*id))
+                        .filter(|id| {
+                            let (tx, ty) = (treedata.list_x[*id], treedata.list_y[*id]);
+                            x1 <= tx && tx <= x2 && y1 <= ty && ty <= y2
+                        })
+                        .map(|id| treedata.names[id].clone())
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                    if !list_sel.is_empty() {
+                        ctx.props().on_propagate.emit(MsgCore::SetStrainsSelected(list_sel, true));
+                    }
+                    return true;
+                }
+
+                //Click on a tip toggles it; click on a branch toggles its whole clade
+                if let Some((dx,dy)) = down_pos {
+                    if (x-dx).abs() < 0.01 && (y-dy).abs() < 0.01 {
+                        let (wx, wy) = self.camera.cam2world(x,y);
+                        let (scale_x, scale_y) = self.world2pixel_scale();
+                        if let Some(id) = treedata.closest_line(wx, wy, scale_x, scale_y, CLICK_DISTANCE) {
+                            let list_sel: Vec<String> = treedata.tips_under(id).into_iter()
+                                .map(|id| treedata.names[id].clone())
+                                .filter(|name| !name.is_empty())
+                                .collect();
+                            let selected = &ctx.props().selected_strains;
+                            let all_selected = list_sel.iter().all(|name| selected.contains(name));
+                            ctx.props().on_propagate.emit(MsgCore::SetStrainsSelected(list_sel, !all_selected));
+                        }
+                    }
+                }
                 false
             },
 
@@ -148,8 +390,24 @@ impl Component for TreeView {
             MsgTree::MouseWheel(e.delta_y() as f32)
         });
 
-        let cb_mouseclicked = ctx.link().callback(move |_e: MouseEvent | { 
-            MsgTree::MouseClick
+        let cb_mousedown = ctx.link().callback(move |e: MouseEvent | {
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            MsgTree::MouseDown(x_cam, y_cam, e.shift_key())
+        });
+
+        let cb_mouseup = ctx.link().callback(move |e: MouseEvent | {
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            MsgTree::MouseUp(x_cam, y_cam)
+        });
+
+        let cb_mouseleave = ctx.link().callback(move |_e: MouseEvent | {
+            MsgTree::MouseLeave
+        });
+
+        let cb_contextmenu = ctx.link().callback(move |e: MouseEvent | {
+            e.prevent_default();
+            let (x_cam, y_cam) = mouseevent_get_cx(&e);
+            MsgTree::ContextMenu(x_cam, y_cam)
         });
         
 
@@ -167,18 +425,21 @@ impl Component for TreeView {
             AsyncData::NotLoaded => {
                 log::debug!("Loading tree");
                 ctx.link().send_message(MsgTree::Propagate(MsgCore::FetchTreeData));                
-                html!{<p>{"Tree not yet loaded. This can take a few seconds on Chrome"}</p>}
+                html!{<p>{"Tree not yet loaded"}</p>}
             },
             AsyncData::Loading => {
-                html!{<p>{"Tree is loading. This can take a few seconds on Chrome"}</p>}
+                html!{<p>{"Tree is loading"}</p>}
             },
             AsyncData::Loaded(_treedata) => {
                 html!{
                     <canvas 
                         ref={self.node_ref.clone()} 
                         style="border:1px solid #000000;"
+                        onmousedown={cb_mousedown}
                         onmousemove={cb_mousemoved} 
-                        onclick={cb_mouseclicked} 
+                        onmouseup={cb_mouseup}
+                        onmouseleave={cb_mouseleave}
+                        oncontextmenu={cb_contextmenu}
                         onwheel={cb_mousewheel} 
                         width={format!{"{}", canvas_w}}
                         height={format!{"{}", canvas_h}}
@@ -188,11 +449,40 @@ impl Component for TreeView {
         };
 
 
+        //Selection rectangle being drawn, as an overlay
+        let html_rect = if let Some((c1, c2)) = &self.select_rect {
+            let to_px = |(wx, wy): (f32,f32)| {
+                let (cx, cy) = self.camera.world2cam(wx, wy);
+                ((cx+1.0)/2.0*canvas_w as f32, (cy+1.0)/2.0*canvas_h as f32)
+            };
+            let (x1, y1) = to_px(*c1);
+            let (x2, y2) = to_px(*c2);
+            html! {
+                <svg width={format!("{}", canvas_w)} height={format!("{}", canvas_h)} style="position:absolute;left:0;top:0;pointer-events:none;">
+                    <rect x={format!("{}", x1.min(x2))} y={format!("{}", y1.min(y2))} width={format!("{}", (x2-x1).abs())} height={format!("{}", (y2-y1).abs())}
+                        style="fill:rgba(0,150,255,0.1);stroke:rgb(0,150,255);stroke-width:1;"/>
+                </svg>
+            }
+        } else {
+            html! {""}
+        };
+
         //Compose the view
         html! {
-            <div style="display: flex; height: 500px; position: relative;">
-                <div style="position: absolute; left:0; top:0; display: flex; ">
-                    {loading_message}
+            <div>
+                <div style="display: flex; height: 500px; position: relative;">
+                    <div style="position: absolute; left:0; top:0; display: flex; ">
+                        {loading_message}
+                        {self.view_labels(ctx, canvas_w, canvas_h)}
+                        {self.view_focus(ctx, canvas_w, canvas_h)}
+                        {self.view_menu(ctx, canvas_w, canvas_h)}
+                        {self.view_tooltip(ctx, canvas_w, canvas_h)}
+                        {html_rect}
+                    </div>
+                </div>
+                { self.coloring.as_ref().map(|c| c.view_legend()).unwrap_or_default() }
+                <div class="commontext">
+                    {"Drag to pan, scroll to zoom. Click a tip to toggle it, click a branch to toggle its clade, shift-drag to select an area. Right-click a branch to collapse or open its clade"}
                 </div>
             </div>
         }
@@ -203,193 +493,553 @@ impl Component for TreeView {
 
 
     ////////////////////////////////////////////////////////////
-    /// Called after DOM has been created
+    /// Called after DOM has been created. GL resources are kept between renders;
+    /// on pan and zoom only the camera is updated
     fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
 
         let async_treedata = &ctx.props().treedata;
 
         if let AsyncData::Loaded(treedata) = &async_treedata {
 
-            //Get list of selected strains
-            let mut list_strainid = Vec::new();
-            if let AsyncData::Loaded(tabledata) = &ctx.props().tabledata {
-                for onerow in &tabledata.rows {
-                    let strain_id = onerow.get(0).expect("no id column");
-                    list_strainid.push(strain_id.clone());
-                }
-            }
-            //log::debug!("strains in table {:?}",list_strainid);
-
-            //Figure out nodes to color
-            let list_sel_node = treedata.get_ids_from_names(&list_strainid);
-            //log::debug!("ids in table {:?}",list_strainid);
-            let list_color = treedata.select_common_ancestors(&list_sel_node);
-            //log::debug!("ids to color {:?}",list_color);
+            // Once rendered, store references for the canvas and GL context. These can be used for
+            // resizing the rendering area when the window or canvas element are resized, as well as
+            // for making GL calls.
+            let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
 
-            //Fit camera whenever we get a new umap to show
+            //Fit camera whenever we get a new tree or layout to show
             if &self.last_treedata != async_treedata {
 //                log::debug!(" fit_reduction ");
-                self.camera.fit_reduction(&treedata.get_bounding_rect());
+                if treedata.shape.keep_aspect() {
+                    self.camera.fit_keep_aspect(&treedata.get_bounding_rect(), canvas.width() as f32 / canvas.height() as f32);
+                } else {
+                    self.camera.fit_reduction(&treedata.get_bounding_rect());
+                }
 //                self.camera.zoom_x *= 2.0;
                 self.last_treedata = async_treedata.clone();
             }
 
+            //Set up program once per canvas, and vertex positions once per tree
+            let same_canvas = self.gl.as_ref().map(|g| g.canvas == canvas).unwrap_or(false);
+            if !same_canvas {
+                self.gl = Some(TreeGl::new(canvas));
+            }
+            let tree_gl = self.gl.as_mut().unwrap();
+            if &tree_gl.treedata != async_treedata {
+                tree_gl.upload_positions(treedata);
+                tree_gl.treedata = async_treedata.clone();
+                tree_gl.colored_for = None;
+            }
+
+            //Colors only change with the search result, selection and coloring by metadata
+            let props = ctx.props();
+            let color_key = (props.tabledata.clone(), props.selection_version, props.tree_metadata.clone(), props.color_column.clone());
+            if tree_gl.colored_for.as_ref() != Some(&color_key) {
+                tree_gl.upload_colors(&Self::line_colors(ctx, treedata, self.coloring.as_ref()));
+                tree_gl.colored_for = Some(color_key);
+            }
+
+            tree_gl.draw(&self.camera);
+        } 
+    }
+}
+
+
+impl TreeView {
 
-            //log::debug!("camera {:?}", self.camera);
+    ////////////////////////////////////////////////////////////
+    /// Index strains in the metadata, when it changes
+    fn update_meta_index(&mut self, ctx: &Context<Self>) {
+        let tree_metadata = &ctx.props().tree_metadata;
+        if &self.meta_for != tree_metadata {
+            self.meta_index = match tree_metadata {
+                AsyncData::Loaded(values) => values.index_by_id(),
+                _ => HashMap::new(),
+            };
+            self.meta_for = tree_metadata.clone();
+        }
+    }
 
-            // Only start the render loop if it's the first render
-            // There's no loop cancellation taking place, so if multiple renders happen,
-            // there would be multiple loops running. That doesn't *really* matter here because
-            // there's no props update and no SSR is taking place, but it is something to keep in
-            // consideration
 
-            // TODO should we only render if data changed?
-            /*
-            if !first_render {
-                return;
+    ////////////////////////////////////////////////////////////
+    /// Color nodes by metadata, when the tree, metadata or column changes
+    fn update_coloring(&mut self, ctx: &Context<Self>) {
+        let props = ctx.props();
+        let key = (props.treedata.clone(), props.tree_metadata.clone(), props.color_column.clone(), props.color_numeric);
+        if self.coloring_for.as_ref() == Some(&key) {
+            return;
+        }
+        self.coloring = match (&props.treedata, &props.tree_metadata) {
+            (AsyncData::Loaded(treedata), AsyncData::Loaded(values)) if values.column(&props.color_column).is_some() => {
+                Some(TreeColoring::new(treedata, values, &self.meta_index, &props.color_column, props.color_numeric))
+            },
+            _ => None,
+        };
+        self.coloring_for = Some(key);
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Find the tips to focus on when a new list is given, and move the camera to them
+    fn update_focus(&mut self, ctx: &Context<Self>) {
+        let props = ctx.props();
+        let treedata = if let AsyncData::Loaded(treedata) = &props.treedata {
+            treedata
+        } else {
+            return;
+        };
+        if self.focus_for == props.focus_strains {
+            return;
+        }
+        self.focus_for = props.focus_strains.clone();
+        self.focus_ids = if let AsyncData::Loaded(list_strains) = &props.focus_strains {
+            treedata.get_ids_from_names(list_strains)
+        } else {
+            Vec::new()
+        };
+
+        if let Some(rect) = treedata.get_bounding_rect_of(&self.focus_ids) {
+            let to = self.camera_around(treedata, rect);
+            self.animation = Some(CameraAnimation {
+                from: self.camera.clone(),
+                to,
+                start: None,
+            });
+            Self::request_animation_frame(ctx);
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Camera showing a part of the tree. Very small parts are enlarged so that there is something around them to see
+    fn camera_around(&self, treedata: &TreeLayout, rect: Rectangle2D) -> Camera2D {
+        let full = treedata.get_bounding_rect();
+        let (min_dx, min_dy) = if treedata.shape.keep_aspect() {
+            let d = (full.x2 - full.x1).max(full.y2 - full.y1) * FOCUS_MIN_FRACTION;
+            (d, d)
+        } else {
+            ((full.x2 - full.x1) * 0.2, FOCUS_MIN_TIPS)
+        };
+        let grow = |a: f32, b: f32, min: f32| {
+            let mid = (a + b) / 2.0;
+            let half = ((b - a) / 2.0).max(min / 2.0);
+            (mid - half, mid + half)
+        };
+        let (x1, x2) = grow(rect.x1, rect.x2, min_dx);
+        let (y1, y2) = grow(rect.y1, rect.y2, min_dy);
+        let rect = Rectangle2D { x1, x2, y1, y2 };
+
+        let mut camera = self.camera.clone();
+        if treedata.shape.keep_aspect() {
+            let (w, h) = if let Some(canvas) = self.node_ref.cast::<HtmlCanvasElement>() {
+                (canvas.width() as f32, canvas.height() as f32)
+            } else {
+                (1.0, 1.0)
+            };
+            camera.fit_keep_aspect(&rect, w / h);
+        } else {
+            camera.fit_reduction(&rect);
+        }
+        camera
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Ask the browser to send the time of the next frame
+    fn request_animation_frame(ctx: &Context<Self>) {
+        let link = ctx.link().clone();
+        let on_frame = Closure::once_into_js(move |time: f64| {
+            link.send_message(MsgTree::AnimationFrame(time));
+        });
+        window().expect("no window").request_animation_frame(on_frame.unchecked_ref()).expect("Could not request animation frame");
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Markers around the tips being focused on
+    fn view_focus(&self, ctx: &Context<Self>, canvas_w: usize, canvas_h: usize) -> Html {
+        let treedata = if let AsyncData::Loaded(treedata) = &ctx.props().treedata {
+            treedata
+        } else {
+            return html! {""};
+        };
+        if self.focus_ids.is_empty() {
+            return html! {""};
+        }
+
+        let html_markers = self.focus_ids.iter().take(MAX_LABELS).map(|id| {
+            let (px, py) = self.world2pixel(treedata.list_x[*id], treedata.list_y[*id], canvas_w, canvas_h);
+            html! {
+                <circle cx={format!("{}", px)} cy={format!("{}", py)} r="6" style="fill:none;stroke:#ff8c00;stroke-width:2;"/>
             }
-            */
-            
+        }).collect::<Html>();
+
+        html! {
+            <svg width={format!("{}", canvas_w)} height={format!("{}", canvas_h)} style="position:absolute;left:0;top:0;pointer-events:none;">
+                {html_markers}
+            </svg>
+        }
+    }
 
-            // Once rendered, store references for the canvas and GL context. These can be used for
-            // resizing the rendering area when the window or canvas element are resized, as well as
-            // for making GL calls.
-            let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
 
-            let gl: GL = canvas
-                .get_context("webgl")
-                .unwrap()
-                .unwrap()
-                .dyn_into()
-                .unwrap();
-
-            let vert_code = String::from(include_str!("./umap.vert"));
-            let frag_code = include_str!("./umap.frag");
-
-            //Get position data
-            let num_lines = treedata.gl_num_lines as usize;
-            let num_points = (num_lines*2) as usize;
-            log::debug!("num_lines {}", num_lines);
-            
-            let mut vec_vertex:Vec<f32> = Vec::new();
-            let vec_vertex_size = 6; //Size of vec3+vec3    /// overkill!!
-            vec_vertex.reserve(num_points * vec_vertex_size);  
-
-            //If we offset all colors to separate part of new array, we can do a memcpy instead
-            for i in 0..num_lines {  //was num points
-                let input_base = i*4;
-                let node_id = treedata.vec_owner.get(i).expect("could not get vec owner");
-
-                let thecol = if list_color.contains(node_id) {
-                    (0.8, 0.06, 0.46)  // #cd1076
+    ////////////////////////////////////////////////////////////
+    /// Name collapsed clades by their number of tips and most common species, when the tree or metadata changes
+    fn update_collapsed_labels(&mut self, ctx: &Context<Self>) {
+        let props = ctx.props();
+        let key = (props.treedata.clone(), props.tree_metadata.clone(), props.species_column.clone());
+        if self.collapsed_labels_for.as_ref() == Some(&key) {
+            return;
+        }
+        self.collapsed_labels.clear();
+        if let AsyncData::Loaded(treedata) = &props.treedata {
+            for id in &treedata.collapsed {
+                let list_tips = treedata.tips_under(*id);
+                let mut counts: HashMap<&String, usize> = HashMap::new();
+                for tip in &list_tips {
+                    if let Some(v) = self.meta_value(ctx, &props.species_column, &treedata.names[*tip]) {
+                        if !v.is_empty() {
+                            *counts.entry(v).or_insert(0) += 1;
+                        }
+                    }
+                }
+                let label = match counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))) {
+                    Some((species, _)) => format!("{} tips, mostly {}", list_tips.len(), species),
+                    None => format!("{} tips", list_tips.len()),
+                };
+                self.collapsed_labels.insert(*id, label);
+            }
+        }
+        self.collapsed_labels_for = Some(key);
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Menu for a clade: collapse or expand it, or show it as a tree of its own
+    fn view_menu(&self, ctx: &Context<Self>, canvas_w: usize, canvas_h: usize) -> Html {
+        let (treedata, id, (x, y)) = match (&ctx.props().treedata, self.menu) {
+            (AsyncData::Loaded(treedata), Some((id, pos))) => (treedata, id, pos),
+            _ => return html! {""},
+        };
+        let collapse_text = if treedata.collapsed.contains(&id) { "Expand clade" } else { "Collapse clade" };
+        let onclick_collapse = ctx.link().batch_callback(move |_e: MouseEvent| vec![
+            MsgTree::Propagate(MsgCore::ToggleTreeCollapse(id)),
+            MsgTree::CloseMenu,
+        ]);
+        let onclick_open = ctx.link().batch_callback(move |_e: MouseEvent| vec![
+            MsgTree::Propagate(MsgCore::OpenSubtree(id)),
+            MsgTree::CloseMenu,
+        ]);
+        let onclick_close = ctx.link().callback(|_e: MouseEvent| MsgTree::CloseMenu);
+
+        let (px, py) = (
+            (x+1.0)/2.0*canvas_w as f32,
+            (y+1.0)/2.0*canvas_h as f32,
+        );
+        html! {
+            <div class="treemenu" style={format!("left:{}px;top:{}px;", px, py)}>
+                <div class="treemenutitle">{format!("Clade of {} tips", treedata.tips_under(id).len())}</div>
+                <button onclick={onclick_collapse}>{collapse_text}</button>
+                <button onclick={onclick_open}>{"Open clade as new tree"}</button>
+                <button onclick={onclick_close}>{"Cancel"}</button>
+            </div>
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Value of a column for a strain, if the metadata has been loaded
+    fn meta_value<'a>(&self, ctx: &'a Context<Self>, column: &str, name: &str) -> Option<&'a String> {
+        if let AsyncData::Loaded(values) = &ctx.props().tree_metadata {
+            let i = self.meta_index.get(name)?;
+            values.column(column).and_then(|list| list.get(*i))
+        } else {
+            None
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Convert from world coordinates to pixels on the canvas
+    fn world2pixel(&self, wx: f32, wy: f32, canvas_w: usize, canvas_h: usize) -> (f32, f32) {
+        let (cx, cy) = self.camera.world2cam(wx, wy);
+        ((cx+1.0)/2.0*canvas_w as f32, (cy+1.0)/2.0*canvas_h as f32)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Labels of the tips in view, if zoomed in far enough for them not to overlap much
+    fn view_labels(&self, ctx: &Context<Self>, canvas_w: usize, canvas_h: usize) -> Html {
+        let treedata = if let AsyncData::Loaded(treedata) = &ctx.props().treedata {
+            treedata
+        } else {
+            return html! {""};
+        };
+
+        let (x_left, y_top) = self.camera.cam2world(-1.0, -1.0);
+        let (x_right, y_bottom) = self.camera.cam2world(1.0, 1.0);
+        let in_view = |x: f32, y: f32| x_left <= x && x <= x_right && y_top <= y && y <= y_bottom;
+
+        //Collapsed clades are always named, at the far end of their triangle
+        let html_collapsed = treedata.collapsed.iter()
+            .filter(|id| !treedata.hidden[**id])
+            .filter_map(|id| {
+                let end = *treedata.children[*id].first()?;
+                let (x, y) = (treedata.list_x[end], treedata.list_y[end]);
+                if !in_view(x, y) {
+                    return None;
+                }
+                let (px, py) = self.world2pixel(x, y, canvas_w, canvas_h);
+                Some(html! {
+                    <text x={format!("{}", px + 4.0)} y={format!("{}", py)} dominant-baseline="middle" style="font-size:12px;font-family:sans-serif;font-style:italic;">
+                        {self.collapsed_labels.get(id).cloned().unwrap_or_default()}
+                    </text>
+                })
+            })
+            .take(MAX_LABELS)
+            .collect::<Html>();
+
+        let list_visible: Vec<usize> = (0..treedata.names.len())
+            .filter(|id| treedata.is_leaf(*id) && !treedata.hidden[*id])
+            .filter(|id| in_view(treedata.list_x[*id], treedata.list_y[*id]))
+            .collect();
+        let spacing = canvas_h as f32 / (list_visible.len().max(1) as f32);
+        let list_visible = if spacing < LABEL_MIN_SPACING || list_visible.len() > MAX_LABELS {
+            Vec::new()
+        } else {
+            list_visible
+        };
+        let font_size = (spacing * 0.8).min(12.0);
+        let label_column = &ctx.props().label_column;
+
+        let html_labels = list_visible.into_iter()
+            .map(|id| {
+                let name = &treedata.names[id];
+                let label = if label_column.is_empty() {
+                    name
                 } else {
-                    (0.0, 0.0, 0.0)
+                    self.meta_value(ctx, label_column, name).unwrap_or(name)
                 };
+                let (px, py) = self.world2pixel(treedata.list_x[id], treedata.list_y[id], canvas_w, canvas_h);
+                html! {
+                    <text x={format!("{}", px + 4.0)} y={format!("{}", py)} dominant-baseline="middle"
+                        style={format!("font-size:{}px;font-family:sans-serif;", font_size)}>
+                        {label.clone()}
+                    </text>
+                }
+            }).collect::<Html>();
+
+        html! {
+            <svg width={format!("{}", canvas_w)} height={format!("{}", canvas_h)} style="position:absolute;left:0;top:0;pointer-events:none;">
+                {html_collapsed}
+                {html_labels}
+            </svg>
+        }
+    }
 
-                vec_vertex.push(*treedata.vec_vertex.get(input_base+0).unwrap());
-                vec_vertex.push(*treedata.vec_vertex.get(input_base+1).unwrap());
-                vec_vertex.push(0.0); // only used for 3d reductions
-                
-                vec_vertex.push(thecol.0); 
-                vec_vertex.push(thecol.1); 
-                vec_vertex.push(thecol.2); 
-
-                vec_vertex.push(*treedata.vec_vertex.get(input_base+2).unwrap());
-                vec_vertex.push(*treedata.vec_vertex.get(input_base+3).unwrap());
-                vec_vertex.push(0.0); // only used for 3d reductions
-
-                vec_vertex.push(thecol.0); 
-                vec_vertex.push(thecol.1); 
-                vec_vertex.push(thecol.2); 
+
+    ////////////////////////////////////////////////////////////
+    /// Tooltip of the node under the mouse. Tips show their metadata, internal nodes their number of tips
+    fn view_tooltip(&self, ctx: &Context<Self>, canvas_w: usize, canvas_h: usize) -> Html {
+        let (treedata, id) = match (&ctx.props().treedata, self.hover) {
+            (AsyncData::Loaded(treedata), Some(id)) => (treedata, id),
+            _ => return html! {""},
+        };
+
+        let lines: Vec<String> = if treedata.is_leaf(id) {
+            let name = &treedata.names[id];
+            let mut lines = vec![name.clone()];
+            for (column, display_name) in &ctx.props().tooltip_columns {
+                if let Some(value) = self.meta_value(ctx, column, name) {
+                    lines.push(format!("{}: {}", display_name, value));
+                }
             }
+            lines
+        } else {
+            vec![self.collapsed_labels.get(&id).cloned().unwrap_or_else(|| format!("{} tips", treedata.tips_under(id).len()))]
+        };
 
-            //Connect vertex array to GL
-            let vertex_buffer = gl.create_buffer().unwrap();
-            let js_vertex = js_sys::Float32Array::from(vec_vertex.as_slice());
-            //let verts = js_sys::Int32Array::from(vertices_int.as_slice());
-            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&vertex_buffer));
-            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_vertex, GL::STATIC_DRAW);
-
-            //Compile vertex shader
-            let vert_shader = gl.create_shader(GL::VERTEX_SHADER).unwrap();
-            gl.shader_source(&vert_shader, vert_code.as_str());
-            gl.compile_shader(&vert_shader);
-
-            
-            /*let msg= gl.get_shader_info_log(&vert_shader);
-            if let Some(msg)=msg {
-                log::debug!("error {}", msg);
-            }*/
-
-            //Compile fragment shader
-            let frag_shader = gl.create_shader(GL::FRAGMENT_SHADER).unwrap();
-            gl.shader_source(&frag_shader, frag_code);
-            gl.compile_shader(&frag_shader);
-
-            //Attach shaders
-            let shader_program = gl.create_program().unwrap();
-            gl.attach_shader(&shader_program, &vert_shader);
-            gl.attach_shader(&shader_program, &frag_shader);
-            gl.link_program(&shader_program);
-            gl.use_program(Some(&shader_program));
-
-            //Size of a float in bytes
-            let sizeof_float = 4;
-
-            //Attach the position vector as an attribute for the GL context.
-            let a_position = gl.get_attrib_location(&shader_program, "a_position") as u32;
-            //log::debug!("a_position {}",a_position);
-            gl.enable_vertex_attrib_array(a_position);
-            gl.vertex_attrib_pointer_with_i32(
-                a_position, 
-                3, 
-                GL::FLOAT, 
-                false, 
-                sizeof_float*6, 
-                0
-            );
-
-            //Attach color vector as an attribute
-            let a_color = gl.get_attrib_location(&shader_program, "a_color") as u32;
-            //log::debug!("a_color {}",a_color);
-            gl.enable_vertex_attrib_array(a_color);
-            gl.vertex_attrib_pointer_with_i32(
-                a_color, 
-                3,
-                GL::FLOAT, 
-                false, 
-                sizeof_float * 6, 
-                sizeof_float * 3
-            );
-
-            //Attach camera attributes
-            let u_camera_x = gl.get_uniform_location(&shader_program, "u_camera_x");
-            let u_camera_y = gl.get_uniform_location(&shader_program, "u_camera_y");
-            let u_camera_zoom_x = gl.get_uniform_location(&shader_program, "u_camera_zoom_x");
-            let u_camera_zoom_y = gl.get_uniform_location(&shader_program, "u_camera_zoom_y");
-            gl.uniform1f(u_camera_x.as_ref(), self.camera.x as f32);
-            gl.uniform1f(u_camera_y.as_ref(), self.camera.y as f32);
-            gl.uniform1f(u_camera_zoom_x.as_ref(), self.camera.zoom_x as f32);
-            gl.uniform1f(u_camera_zoom_y.as_ref(), self.camera.zoom_y as f32);
-
-            //log::debug!("canvas {} {}   {:?}", canvas.width(), canvas.height(), self.camera);
-
-            let u_display_w = gl.get_uniform_location(&shader_program, "u_display_w");
-            let u_display_h = gl.get_uniform_location(&shader_program, "u_display_h");
-            gl.uniform1f(u_display_w.as_ref(), canvas.width() as f32);
-            gl.uniform1f(u_display_h.as_ref(), canvas.height() as f32);
-
-            // clear canvas
-            gl.clear_color(1.0, 1.0, 1.0, 1.0);
-            gl.clear(GL::COLOR_BUFFER_BIT);
-            
-            // to make round points, need to draw square https://stackoverflow.com/questions/7237086/opengl-es-2-0-equivalent-for-es-1-0-circles-using-gl-point-smooth
-            gl.draw_arrays(GL::LINES, 0, num_points as i32);
-        } 
+        let (px, py) = (
+            (self.last_pos.0+1.0)/2.0*canvas_w as f32,
+            (self.last_pos.1+1.0)/2.0*canvas_h as f32,
+        );
+        html! {
+            <div class="treetooltip" style={format!("left:{}px;top:{}px;", px + 12.0, py + 12.0)}>
+                { lines.into_iter().map(|line| html! { <div>{line}</div> }).collect::<Html>() }
+            </div>
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Scale from world coordinates to pixels, for each axis
+    fn world2pixel_scale(&self) -> (f32, f32) {
+        let (w, h) = if let Some(canvas) = self.node_ref.cast::<HtmlCanvasElement>() {
+            (canvas.width() as f32, canvas.height() as f32)
+        } else {
+            (1.0, 1.0)
+        };
+        (self.camera.zoom_x * w / 2.0, self.camera.zoom_y * h / 2.0)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Color of each line, as RGB per vertex. Strains in the table, and their common ancestors, are highlighted,
+    /// unless coloring by metadata; selected strains are highlighted above either
+    fn line_colors(ctx: &Context<Self>, treedata: &TreeLayout, coloring: Option<&TreeColoring>) -> Vec<f32> {
+
+        //Get list of strains in the table
+        let mut list_strainid = Vec::new();
+        if let AsyncData::Loaded(tabledata) = &ctx.props().tabledata {
+            for onerow in &tabledata.rows {
+                let strain_id = onerow.get(0).expect("no id column");
+                list_strainid.push(strain_id.clone());
+            }
+        }
+
+        //Figure out nodes to color
+        let list_sel_node = treedata.get_ids_from_names(&list_strainid);
+        let list_color = treedata.select_common_ancestors(&list_sel_node);
+
+        let list_selected: Vec<String> = ctx.props().selected_strains.iter().cloned().collect();
+        let list_selected = treedata.select_common_ancestors(&treedata.get_ids_from_names(&list_selected));
+
+        let mut vec_color: Vec<f32> = Vec::with_capacity(treedata.vec_owner.len() * 6);
+        for node_id in &treedata.vec_owner {
+            let meta_color = coloring.and_then(|c| c.node_color[*node_id]);
+            let thecol = if list_selected.contains(node_id) {
+                (0.0, 0.59, 1.0)  // #0096ff
+            } else if let Some(c) = meta_color {
+                c
+            } else if coloring.is_none() && list_color.contains(node_id) {
+                (0.8, 0.06, 0.46)  // #cd1076
+            } else {
+                (0.0, 0.0, 0.0)
+            };
+            //Same color at both ends of the line
+            for _ in 0..2 {
+                vec_color.push(thecol.0);
+                vec_color.push(thecol.1);
+                vec_color.push(thecol.2);
+            }
+        }
+        vec_color
     }
 }
 
 
+////////////////////////////////////////////////////////////
+/// GL program and buffers of a canvas, kept between renders
+struct TreeGl {
+    canvas: HtmlCanvasElement,
+    gl: GL,
+    program: WebGlProgram,
+    position_buffer: WebGlBuffer,
+    color_buffer: WebGlBuffer,
+    num_points: i32,
+    /// Tree in the position buffer
+    treedata: AsyncData<TreeLayout>,
+    /// What the color buffer was computed for
+    colored_for: Option<ColorKey>,
+}
+impl TreeGl {
+
+    ////////////////////////////////////////////////////////////
+    /// Compile shaders and create buffers
+    fn new(canvas: HtmlCanvasElement) -> TreeGl {
+        let gl: GL = canvas
+            .get_context("webgl")
+            .unwrap()
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+
+        //Compile vertex shader
+        let vert_shader = gl.create_shader(GL::VERTEX_SHADER).unwrap();
+        gl.shader_source(&vert_shader, include_str!("./umap.vert"));
+        gl.compile_shader(&vert_shader);
+
+        //Compile fragment shader
+        let frag_shader = gl.create_shader(GL::FRAGMENT_SHADER).unwrap();
+        gl.shader_source(&frag_shader, include_str!("./umap.frag"));
+        gl.compile_shader(&frag_shader);
+
+        //Attach shaders
+        let program = gl.create_program().unwrap();
+        gl.attach_shader(&program, &vert_shader);
+        gl.attach_shader(&program, &frag_shader);
+        gl.link_program(&program);
+
+        let position_buffer = gl.create_buffer().unwrap();
+        let color_buffer = gl.create_buffer().unwrap();
+
+        TreeGl {
+            canvas,
+            gl,
+            program,
+            position_buffer,
+            color_buffer,
+            num_points: 0,
+            treedata: AsyncData::NotLoaded,
+            colored_for: None,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Upload line end points. The layout is already x,y per vertex
+    fn upload_positions(&mut self, treedata: &TreeLayout) {
+        let js_vertex = js_sys::Float32Array::from(treedata.vec_vertex.as_slice());
+        self.gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.position_buffer));
+        self.gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_vertex, GL::STATIC_DRAW);
+        self.num_points = (treedata.gl_num_lines * 2) as i32;
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Upload colors, RGB per vertex
+    fn upload_colors(&mut self, vec_color: &Vec<f32>) {
+        let js_color = js_sys::Float32Array::from(vec_color.as_slice());
+        self.gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.color_buffer));
+        self.gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_color, GL::DYNAMIC_DRAW);
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Draw the tree as seen by the camera
+    fn draw(&self, camera: &Camera2D) {
+        let gl = &self.gl;
+        let program = &self.program;
+        gl.use_program(Some(program));
+
+        //Size of a float in bytes
+        let sizeof_float = 4;
+
+        //Attach the position vector as an attribute for the GL context.
+        let a_position = gl.get_attrib_location(program, "a_position") as u32;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.position_buffer));
+        gl.enable_vertex_attrib_array(a_position);
+        gl.vertex_attrib_pointer_with_i32(a_position, 2, GL::FLOAT, false, sizeof_float * 2, 0);
+
+        //Attach color vector as an attribute
+        let a_color = gl.get_attrib_location(program, "a_color") as u32;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.color_buffer));
+        gl.enable_vertex_attrib_array(a_color);
+        gl.vertex_attrib_pointer_with_i32(a_color, 3, GL::FLOAT, false, sizeof_float * 3, 0);
+
+        //Attach camera attributes
+        gl.uniform1f(gl.get_uniform_location(program, "u_camera_x").as_ref(), camera.x);
+        gl.uniform1f(gl.get_uniform_location(program, "u_camera_y").as_ref(), camera.y);
+        gl.uniform1f(gl.get_uniform_location(program, "u_camera_zoom_x").as_ref(), camera.zoom_x);
+        gl.uniform1f(gl.get_uniform_location(program, "u_camera_zoom_y").as_ref(), camera.zoom_y);
+        gl.uniform1f(gl.get_uniform_location(program, "u_display_w").as_ref(), self.canvas.width() as f32);
+        gl.uniform1f(gl.get_uniform_location(program, "u_display_h").as_ref(), self.canvas.height() as f32);
+
+        // clear canvas
+        gl.clear_color(1.0, 1.0, 1.0, 1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+        gl.draw_arrays(GL::LINES, 0, self.num_points);
+    }
+}
+
 
 
 
@@ -1,13 +1,103 @@
+use std::collections::HashSet;
+
 use crate::{core_model::*};
+use crate::appstate::AsyncData;
+use crate::treeview::treelayout::TreeShape;
 
+use my_web_app::DatabaseColumn;
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, HtmlInputElement, HtmlSelectElement};
 use yew::{prelude::*};
 
 use super::TreeView;
 
 
+/// Column used to name collapsed clades by their most common value
+const SPECIES_COLUMN: &str = "GTDB_Species";
+
+
 impl Model {
 
 
+    ////////////////////////////////////////////////////////////
+    /// Columns shown in tree tooltips: those shown in the table by default
+    fn tree_tooltip_columns(&self) -> Vec<&DatabaseColumn> {
+        if let Some(db_metadata) = &self.db_metadata {
+            db_metadata.columns.values()
+                .filter(|c| c.display && c.default_show_column && c.column_id != "BTyperDB_ID")
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Columns to get values of for all strains, to label and color the tree, and for tooltips
+    pub fn tree_metadata_columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = self.tree_tooltip_columns().iter().map(|c| c.column_id.clone()).collect();
+        let species_column = self.tree_species_column();
+        for col in [&self.tree_label_column, &self.tree_color_column, &species_column] {
+            if !col.is_empty() && !columns.contains(col) {
+                columns.push(col.clone());
+            }
+        }
+        columns
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Column to name collapsed clades by, if the database has it
+    fn tree_species_column(&self) -> String {
+        match &self.db_metadata {
+            Some(db_metadata) if db_metadata.columns.contains_key(SPECIES_COLUMN) => SPECIES_COLUMN.to_string(),
+            _ => "".to_string(),
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Have the values of a column been loaded for the tree? Trivially true for no column
+    pub fn has_tree_metadata(&self, column: &String) -> bool {
+        match &self.tree_metadata {
+            AsyncData::Loaded(values) => column.is_empty() || values.column(column).is_some(),
+            _ => false,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Strains in the tree matching a query: an exact ID, part of an ID, or a metadata value.
+    /// Case is ignored except for exact IDs
+    pub fn find_in_tree(&self, query: &str) -> Vec<String> {
+        let query = query.trim();
+        let treedata = match &self.treedata {
+            AsyncData::Loaded(treedata) if !query.is_empty() => treedata,
+            _ => return Vec::new(),
+        };
+        if treedata.map_name_to_id.contains_key(query) {
+            return vec![query.to_string()];
+        }
+
+        let query = query.to_lowercase();
+        let mut found: HashSet<&String> = treedata.map_name_to_id.keys()
+            .filter(|name| name.to_lowercase().contains(&query))
+            .collect();
+        if let AsyncData::Loaded(values) = &self.tree_metadata {
+            for list in &values.values {
+                for (i, v) in list.iter().enumerate() {
+                    if v.to_lowercase() == query && treedata.map_name_to_id.contains_key(&values.ids[i]) {
+                        found.insert(&values.ids[i]);
+                    }
+                }
+            }
+        }
+        let mut found: Vec<String> = found.into_iter().cloned().collect();
+        found.sort();
+        found
+    }
+
+
     ////////////////////////////////////////////////////////////
     /// x
     pub fn view_tree_pane(&self, ctx: &Context<Self>) -> Html {
@@ -27,10 +117,152 @@ impl Model {
             sig
         });
          
+        //Selection made in the tree
+        let mut list_selected: Vec<String> = self.selected_strains.iter().cloned().collect();
+        list_selected.sort();
+        let list_show = list_selected.clone();
+        let onclick_show = ctx.link().batch_callback(move |_e: MouseEvent| {
+            vec![MsgCore::StartQueryByID(list_show.clone()), MsgCore::OpenPage(CurrentPage::Search)]
+        });
+        let onclick_clear = ctx.link().callback(move |_e: MouseEvent| MsgCore::SetStrainsSelected(list_selected.clone(), false));
+        let html_selection = html! {
+            <div class="commontext">
+                {format!("{} strains selected ", self.selected_strains.len())}
+                <button class="buttonspacer" disabled={self.selected_strains.is_empty()} onclick={onclick_show}>{"Show in table"}</button>
+                <button class="buttonspacer" disabled={self.selected_strains.is_empty()} onclick={onclick_clear}>{"Clear selection"}</button>
+            </div>
+        };
+
+        //Dropdowns: columns to label tips and color branches by
+        let select_value = |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()).expect("wrong type");
+            input.value()
+        };
+        let onchange_label = ctx.link().callback(move |e: Event| MsgCore::SetTreeLabelColumn(select_value(e)));
+        let onchange_color = ctx.link().callback(move |e: Event| MsgCore::SetTreeColorColumn(select_value(e)));
+        let mut color_numeric = false;
+        let html_label = if let Some(db_metadata) = &self.db_metadata {
+            let select_column = |current: &String, empty_name: &str, on_change: Callback<Event>| html! {
+                <select class="columndrop" onchange={on_change}>
+                    <option value="" selected={current.is_empty()}>{empty_name.to_string()}</option>
+                    {
+                        db_metadata.columns_by_category(|c| c.display && c.column_id != "BTyperDB_ID").into_iter().map(|(category, list_col)| {
+                            html! {
+                                <optgroup label={category}>
+                                {
+                                    list_col.into_iter().map(|col| html! {
+                                        <option value={col.column_id.clone()} selected={&col.column_id == current} title={col.description.clone()}>
+                                            { col.display_name() }
+                                        </option>
+                                    }).collect::<Html>()
+                                }
+                                </optgroup>
+                            }
+                        }).collect::<Html>()
+                    }
+                </select>
+            };
+            color_numeric = db_metadata.columns.get(&self.tree_color_column).map(|c| c.column_type.is_numeric()).unwrap_or(false);
+            html! {
+                <div class="commontext">
+                    <span>{"Label tips by: "}</span>
+                    { select_column(&self.tree_label_column, "BTyperDB ID", onchange_label) }
+                    <span>{" Color by: "}</span>
+                    { select_column(&self.tree_color_column, "Search result", onchange_color) }
+                </div>
+            }
+        } else {
+            html! {""}
+        };
+        //Shape of the tree
+        let cladogram = self.tree_cladogram;
+        let onchange_shape = ctx.link().callback(move |e: Event| {
+            let shape = TreeShape::from_name(&select_value(e)).unwrap_or(TreeShape::Rectangular);
+            MsgCore::SetTreeLayout(shape, cladogram)
+        });
+        let shape = self.tree_shape;
+        let onchange_cladogram = ctx.link().callback(move |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).expect("wrong type");
+            MsgCore::SetTreeLayout(shape, input.checked())
+        });
+        let html_shape = html! {
+            <div class="commontext">
+                <span>{"Layout: "}</span>
+                <select class="columndrop" onchange={onchange_shape}>
+                {
+                    TreeShape::all().into_iter().map(|s| html! {
+                        <option value={s.name()} selected={s == self.tree_shape}>{s.name()}</option>
+                    }).collect::<Html>()
+                }
+                </select>
+                <label class="buttonspacer">
+                    <input type="checkbox" checked={self.tree_cladogram} onchange={onchange_cladogram}/>
+                    {" Ignore branch lengths (cladogram)"}
+                </label>
+            </div>
+        };
+
+        //Find strains in the tree, and zoom to search hits
+        let onchange_find = ctx.link().callback(move |e: Event| {
+            let target: Option<EventTarget> = e.target();
+            let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).expect("wrong type");
+            MsgCore::FindInTree(input.value())
+        });
+        let list_hits: Vec<String> = if let AsyncData::Loaded(tabledata) = &self.tabledata {
+            tabledata.rows.iter().filter_map(|row| row.get(0).cloned()).collect()
+        } else {
+            Vec::new()
+        };
+        let has_hits = !list_hits.is_empty();
+        let onclick_zoom_hits = ctx.link().callback(move |_e: MouseEvent| MsgCore::FocusTree(list_hits.clone()));
+        let found_text = match &self.tree_focus {
+            AsyncData::Loaded(list_found) if !self.tree_find.is_empty() => {
+                if list_found.is_empty() {
+                    "No strains found".to_string()
+                } else {
+                    format!("{} strains found", list_found.len())
+                }
+            },
+            _ => "".to_string(),
+        };
+        let html_find = html! {
+            <div class="commontext">
+                <span>{"Find strain: "}</span>
+                <input class="textbox" type="text" placeholder="ID or metadata value" value={self.tree_find.clone()} onchange={onchange_find}/>
+                <span class="buttonspacer">{found_text}</span>
+                <button class="buttonspacer" disabled={!has_hits} onclick={onclick_zoom_hits}>{"Zoom to search hits"}</button>
+            </div>
+        };
+
+        //Clade being shown instead of the whole tree
+        let html_subtree = match &self.treedata {
+            AsyncData::Loaded(treedata) if self.tree_is_subtree => {
+                let num_tips = treedata.tips_under(treedata.root()).len();
+                html! {
+                    <div class="commontext">
+                        {format!("Showing a clade of {} tips ", num_tips)}
+                        <button class="buttonspacer" onclick={ctx.link().callback(|_e: MouseEvent| MsgCore::ShowFullTree)}>{"Back to full tree"}</button>
+                    </div>
+                }
+            },
+            _ => html! {""},
+        };
+
+        let tooltip_columns: Vec<(String, String)> = self.tree_tooltip_columns().iter()
+            .map(|c| (c.column_id.clone(), c.display_name_with_unit()))
+            .collect();
+
         html! {
             <div>
                 <div> 
                     <div class="App-divider">{"Tree view"}</div>
+                    {html_selection}
+                    {html_subtree}
+                    {html_find}
+                    {html_shape}
+                    {html_label}
                     <TreeView 
 //                        on_cell_hovered={on_cell_hovered} 
 //                        on_cell_clicked={on_cell_clicked} 
@@ -38,6 +270,15 @@ impl Model {
                         on_propagate={on_propagate}
                         last_component_size={self.last_component_size.clone()}
                         tabledata={self.tabledata.clone()}
+                        selected_strains={self.selected_strains.clone()}
+                        selection_version={self.selection_version}
+                        tree_metadata={self.tree_metadata.clone()}
+                        label_column={self.tree_label_column.clone()}
+                        tooltip_columns={tooltip_columns}
+                        color_column={self.tree_color_column.clone()}
+                        color_numeric={color_numeric}
+                        focus_strains={self.tree_focus.clone()}
+                        species_column={self.tree_species_column()}
                     />
                 </div>
             </div>
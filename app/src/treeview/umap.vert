@@ -18,6 +18,8 @@ uniform float u_camera_zoom_y;
 uniform float u_display_w;
 uniform float u_display_h;
 
+uniform float u_point_size;
+
 
 void main() {
 
@@ -32,7 +34,7 @@ void main() {
     gl_Position = vec4(scaled.x, -scaled.y, 0.0, 1.0);   // Invert camera y to match 
 
     //Set size of points
-    gl_PointSize = 2.0;
+    gl_PointSize = u_point_size;
 
     //Set color
 //    color = vec3(0.0, 0.0, 0.0);
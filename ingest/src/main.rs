@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+use my_web_app::{is_iso_date, read_database_columns, ColumnType, DatabaseColumn};
+
+
+/// Name of the column with strain IDs. The server expects it to be the first column
+const ID_COLUMN: &str = "BTyperDB_ID";
+
+
+////////////////////////////////////////////////////////////
+/// Command line arguments
+#[derive(Debug, Parser)]
+#[command(about = "Build meta/data.sqlite for BTyperDB from the master metadata TSV")]
+struct Args {
+    /// Master metadata table, tab separated
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Column definitions, btyperdb_include.tsv
+    #[arg(long)]
+    columns: PathBuf,
+
+    /// SQLite database to create
+    #[arg(long)]
+    output: PathBuf,
+
+    /// Write all values failing type checks to this TSV file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Replace the output database if it exists
+    #[arg(long)]
+    force: bool,
+
+    /// Exit with an error if any value fails type checks
+    #[arg(long)]
+    strict: bool,
+}
+
+
+////////////////////////////////////////////////////////////
+/// A value that could not be stored with the type of its column
+#[derive(Debug)]
+struct TypeError {
+    row: usize,
+    strain_id: String,
+    column: String,
+    column_type: String,
+    value: String,
+}
+
+
+////////////////////////////////////////////////////////////
+/// SQLite type to use for a column type in btyperdb_include.tsv
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Text | ColumnType::Date => "TEXT",
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Convert one value from the TSV into the type of the column. Empty values become NULL.
+/// Returns None if the value does not have the right type
+fn typed_value(column_type: ColumnType, s: &str) -> Option<Value> {
+    let s = s.trim();
+    if s.is_empty() || s == "NA" {
+        return Some(Value::Null);
+    }
+    match column_type {
+        ColumnType::Integer => s.parse::<i64>().ok().map(Value::Integer),
+        ColumnType::Float => s.parse::<f64>().ok().map(Value::Real),
+        ColumnType::Boolean => match s.to_lowercase().as_str() {
+            "1" | "true" | "yes" => Some(Value::Integer(1)),
+            "0" | "false" | "no" => Some(Value::Integer(0)),
+            _ => None,
+        },
+        ColumnType::Date => if is_iso_date(s) {
+            Some(Value::Text(s.to_string()))
+        } else {
+            None
+        },
+        ColumnType::Text => Some(Value::Text(s.to_string())),
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Quote a column name for SQL
+fn sql_name(s: &str) -> String {
+    format!("`{}`", s.replace("`", "``"))
+}
+
+
+////////////////////////////////////////////////////////////
+/// Ingest entry point
+fn main() {
+    let args = Args::parse();
+
+    if args.output.exists() {
+        if args.force {
+            std::fs::remove_file(&args.output).expect("Could not remove old database");
+        } else {
+            eprintln!("{} already exists; use --force to replace it", args.output.display());
+            std::process::exit(1);
+        }
+    }
+
+    // Read column definitions
+    let f_columns = File::open(&args.columns).unwrap_or_else(|e| panic!("Could not open {}: {}", args.columns.display(), e));
+    let list_columns = read_database_columns(BufReader::new(f_columns)).expect("Could not parse column definitions");
+    let map_columns: HashMap<String, DatabaseColumn> = list_columns.iter().map(|c| (c.column_id.clone(), c.clone())).collect();
+
+    // Read header of the master table
+    let f_input = File::open(&args.input).unwrap_or_else(|e| panic!("Could not open {}: {}", args.input.display(), e));
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(BufReader::new(f_input));
+    let header: Vec<String> = reader.headers().expect("Could not read header").iter().map(|s| s.to_string()).collect();
+
+    // Columns in table order, with the ID column first
+    let mut order: Vec<usize> = (0..header.len()).collect();
+    match header.iter().position(|h| h == ID_COLUMN) {
+        Some(pos) => {
+            order.remove(pos);
+            order.insert(0, pos);
+        },
+        None => {
+            eprintln!("Input has no {} column", ID_COLUMN);
+            std::process::exit(1);
+        }
+    }
+
+    // Type of each column. Columns without a definition are kept as text
+    let mut column_types = Vec::new();
+    for h in &header {
+        if let Some(col) = map_columns.get(h) {
+            column_types.push(col.column_type);
+        } else {
+            println!("Warning: column {} is not in {}, stored as text", h, args.columns.display());
+            column_types.push(ColumnType::Text);
+        }
+    }
+    for col in &list_columns {
+        if !header.contains(&col.column_id) {
+            println!("Warning: column {} is defined but not in the input", col.column_id);
+        }
+    }
+
+    // Create the table
+    let mut conn = Connection::open(&args.output).expect("Could not create database");
+    let create = format!(
+        "CREATE TABLE straindata ({})",
+        order.iter().map(|i| format!("{} {}", sql_name(&header[*i]), sql_type(column_types[*i]))).collect::<Vec<_>>().join(", ")
+    );
+    conn.execute(&create, []).expect("Could not create table");
+
+    // Insert all rows
+    let mut list_errors: Vec<TypeError> = Vec::new();
+    let mut num_rows = 0;
+    let tx = conn.transaction().expect("Could not start transaction");
+    {
+        let insert = format!(
+            "INSERT INTO straindata VALUES ({})",
+            vec!["?"; order.len()].join(",")
+        );
+        let mut stmt = tx.prepare(&insert).expect("Could not prepare insert");
+
+        for (row_i, record) in reader.records().enumerate() {
+            let record = record.expect("Could not read row");
+            let strain_id = record.get(order[0]).unwrap_or("").to_string();
+
+            let mut values = Vec::with_capacity(order.len());
+            for i in &order {
+                let s = record.get(*i).unwrap_or("");
+                match typed_value(column_types[*i], s) {
+                    Some(v) => values.push(v),
+                    None => {
+                        list_errors.push(TypeError {
+                            row: row_i + 2, // 1-based, after the header
+                            strain_id: strain_id.clone(),
+                            column: header[*i].clone(),
+                            column_type: column_types[*i].as_str().to_string(),
+                            value: s.to_string(),
+                        });
+                        values.push(Value::Null);
+                    }
+                }
+            }
+            stmt.execute(params_from_iter(values.iter())).expect("Could not insert row");
+            num_rows += 1;
+        }
+    }
+    tx.commit().expect("Could not commit");
+
+    // Index all columns that can be searched
+    for (i, col) in list_columns.iter().enumerate() {
+        if col.search && header.contains(&col.column_id) {
+            let q = format!("CREATE INDEX idx_{} ON straindata ({})", i, sql_name(&col.column_id));
+            conn.execute(&q, []).expect("Could not create index");
+        }
+    }
+
+    // Report
+    println!("Inserted {} rows, {} columns into {}", num_rows, header.len(), args.output.display());
+    if !list_errors.is_empty() {
+        println!("{} values failed type checks and were stored as NULL:", list_errors.len());
+        for e in list_errors.iter().take(20) {
+            println!("  line {} ({}): {} is {}, got \"{}\"", e.row, e.strain_id, e.column, e.column_type, e.value);
+        }
+        if list_errors.len() > 20 {
+            println!("  ...");
+        }
+    }
+    if let Some(path_report) = &args.report {
+        let mut f = File::create(path_report).expect("Could not create report");
+        writeln!(f, "line\tstrain\tcolumn\ttype\tvalue").unwrap();
+        for e in &list_errors {
+            writeln!(f, "{}\t{}\t{}\t{}\t{}", e.row, e.strain_id, e.column, e.column_type, e.value).unwrap();
+        }
+    }
+    if args.strict && !list_errors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sql_types() {
+        assert_eq!(sql_type(ColumnType::Integer), "INTEGER");
+        assert_eq!(sql_type(ColumnType::Boolean), "INTEGER");
+        assert_eq!(sql_type(ColumnType::Float), "REAL");
+        assert_eq!(sql_type(ColumnType::Date), "TEXT");
+        assert_eq!(sql_type(ColumnType::Text), "TEXT");
+    }
+
+    #[test]
+    fn integer_values() {
+        assert_eq!(typed_value(ColumnType::Integer, " 42 "), Some(Value::Integer(42)));
+        assert_eq!(typed_value(ColumnType::Integer, "-7"), Some(Value::Integer(-7)));
+        assert_eq!(typed_value(ColumnType::Integer, "4.5"), None);
+        assert_eq!(typed_value(ColumnType::Integer, "many"), None);
+    }
+
+    #[test]
+    fn float_values() {
+        assert_eq!(typed_value(ColumnType::Float, "0.25"), Some(Value::Real(0.25)));
+        assert_eq!(typed_value(ColumnType::Float, "3"), Some(Value::Real(3.0)));
+        assert_eq!(typed_value(ColumnType::Float, "1,5"), None);
+    }
+
+    #[test]
+    fn boolean_values() {
+        assert_eq!(typed_value(ColumnType::Boolean, "Yes"), Some(Value::Integer(1)));
+        assert_eq!(typed_value(ColumnType::Boolean, "0"), Some(Value::Integer(0)));
+        assert_eq!(typed_value(ColumnType::Boolean, "maybe"), None);
+    }
+
+    #[test]
+    fn date_values() {
+        assert_eq!(typed_value(ColumnType::Date, "2019"), Some(Value::Text("2019".to_string())));
+        assert_eq!(typed_value(ColumnType::Date, "2019-03-12"), Some(Value::Text("2019-03-12".to_string())));
+        assert_eq!(typed_value(ColumnType::Date, "12/03/2019"), None);
+        assert_eq!(typed_value(ColumnType::Date, "2019-3-12"), None);
+    }
+
+    #[test]
+    fn empty_values_are_null() {
+        for t in [ColumnType::Text, ColumnType::Integer, ColumnType::Float, ColumnType::Date, ColumnType::Boolean].iter() {
+            assert_eq!(typed_value(*t, ""), Some(Value::Null));
+            assert_eq!(typed_value(*t, " NA "), Some(Value::Null));
+        }
+        assert_eq!(typed_value(ColumnType::Text, " some text "), Some(Value::Text("some text".to_string())));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+
+use my_web_app::newick::parse_newick;
+use my_web_app::read_database_columns;
+
+use crate::config::{check_store_layout, ReleaseConfig};
+use crate::stats::StatsConfig;
+
+
+/// How many problems of one kind to list before summarizing
+const MAX_LISTED: usize = 20;
+
+
+////////////////////////////////////////////////////////////
+/// Problems found in one store
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+impl CheckReport {
+
+    ////////////////////////////////////////////////////////////
+    /// Add one error for each item, listing only the first few
+    fn add_list(&mut self, what: &str, list: &[String]) {
+        if list.is_empty() {
+            return;
+        }
+        let mut shown: Vec<String> = list.iter().take(MAX_LISTED).cloned().collect();
+        if list.len() > MAX_LISTED {
+            shown.push("...".to_string());
+        }
+        self.errors.push(format!("{} {}: {}", list.len(), what, shown.join(", ")));
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Check that database, tree, genomes and column definitions of a store agree with each other
+pub fn check_store(path_store: &Path) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    //Without the files in place, nothing else can be checked
+    report.errors.extend(check_store_layout(path_store));
+    if !report.errors.is_empty() {
+        return report;
+    }
+
+    // Open SQL database
+    let path_sql = path_store.join("meta/data.sqlite");
+    let conn = match Connection::open_with_flags(&path_sql, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(conn) => conn,
+        Err(e) => {
+            report.errors.push(format!("Could not open SQL database {}: {}", path_sql.display(), e));
+            return report;
+        }
+    };
+
+    // Columns and strains in the database
+    let db_columns: HashSet<String> = match conn.prepare("SELECT * FROM straindata LIMIT 0") {
+        Ok(stmt) => stmt.column_names().iter().map(|x| x.to_string()).collect(),
+        Err(e) => {
+            report.errors.push(format!("Could not read strain table: {}", e));
+            return report;
+        }
+    };
+    let db_strains: Vec<String> = match query_strain_list(&conn) {
+        Ok(list) => list,
+        Err(e) => {
+            report.errors.push(format!("Could not read strain IDs: {}", e));
+            return report;
+        }
+    };
+    let set_strains: HashSet<&String> = db_strains.iter().collect();
+
+    // Every defined column must be in the table
+    let path_meta = path_store.join("meta/btyperdb_include.tsv");
+    match File::open(&path_meta).map(BufReader::new) {
+        Ok(f) => match read_database_columns(f) {
+            Ok(list_columns) => {
+                let missing: Vec<String> = list_columns.iter()
+                    .map(|c| c.column_id.clone())
+                    .filter(|c| !db_columns.contains(c))
+                    .collect();
+                report.add_list("columns in btyperdb_include.tsv are not in the strain table", &missing);
+            },
+            Err(e) => report.errors.push(format!("Could not parse {}: {}", path_meta.display(), e)),
+        },
+        Err(e) => report.errors.push(format!("Could not open {}: {}", path_meta.display(), e)),
+    }
+
+    // Columns used by the statistics page
+    match StatsConfig::read(path_store) {
+        Ok(stats_config) => {
+            let mut used: Vec<String> = stats_config.charts.iter().map(|c| c.column.clone()).collect();
+            if let Some(location) = &stats_config.location {
+                used.push(location.latitude.clone());
+                used.push(location.longitude.clone());
+            }
+            let missing: Vec<String> = used.into_iter()
+                .filter(|c| !db_columns.contains(c))
+                .collect();
+            report.add_list("columns used for statistics are not in the strain table", &missing);
+        },
+        Err(e) => report.errors.push(e),
+    }
+
+    // Every strain must have a genome
+    let path_fna = path_store.join("fna");
+    let mut genomes = HashSet::new();
+    if let Ok(list_files) = std::fs::read_dir(&path_fna) {
+        for entry in list_files.flatten() {
+            let fname = entry.file_name().to_string_lossy().to_string();
+            if let Some(id) = fname.strip_suffix(".fna.gz") {
+                genomes.insert(id.to_string());
+            }
+        }
+    }
+    let missing: Vec<String> = db_strains.iter()
+        .filter(|id| !genomes.contains(*id))
+        .cloned()
+        .collect();
+    report.add_list("strains have no genome in fna/", &missing);
+
+    let extra = genomes.iter().filter(|id| !set_strains.contains(id)).count();
+    if extra > 0 {
+        report.warnings.push(format!("{} genomes in fna/ are not in the strain table", extra));
+    }
+
+    // Every tip in the tree must be a strain
+    let path_tree = path_store.join("tree.nwk");
+    match std::fs::read_to_string(&path_tree) {
+        Ok(tree_str) => match parse_newick(&tree_str) {
+            Ok(tree) => {
+                let missing: Vec<String> = tree.tip_names().into_iter()
+                    .filter(|name| !set_strains.contains(name))
+                    .collect();
+                report.add_list("tips in tree.nwk are not in the strain table", &missing);
+            },
+            Err(e) => report.errors.push(format!("Could not parse {}: {}", path_tree.display(), e)),
+        },
+        Err(e) => report.errors.push(format!("Could not read {}: {}", path_tree.display(), e)),
+    }
+
+    report
+}
+
+
+////////////////////////////////////////////////////////////
+/// Get the IDs of all strains
+fn query_strain_list(
+    conn: &Connection
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT BTyperDB_ID FROM straindata")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+
+////////////////////////////////////////////////////////////
+/// Check all releases and print a report. Returns true if no errors were found
+pub fn check_releases(releases: &[ReleaseConfig]) -> bool {
+    let mut ok = true;
+    for r in releases {
+        println!("Release {} ({})", r.name, r.store);
+        let report = check_store(Path::new(&r.store));
+        for w in &report.warnings {
+            println!("  warning: {}", w);
+        }
+        for e in &report.errors {
+            println!("  error: {}", e);
+        }
+        if report.errors.is_empty() {
+            println!("  ok");
+        } else {
+            ok = false;
+        }
+    }
+    ok
+}
@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use serde::Serialize;
+
+
+////////////////////////////////////////////////////////////
+/// Command line arguments. Each one overrides the config file
+#[derive(Debug, Parser)]
+#[command(about = "BTyperDB web server")]
+pub struct Args {
+    /// Config file to read
+    #[arg(long, env = "BTYPERDB_CONFIG", default_value = "config.json")]
+    pub config: PathBuf,
+
+    /// Directory of the database store
+    #[arg(long, env = "BTYPERDB_STORE")]
+    pub store: Option<String>,
+
+    /// Address to listen on, e.g. 127.0.0.1:8080
+    #[arg(long, env = "BTYPERDB_BIND")]
+    pub bind: Option<String>,
+
+    /// Directory with the compiled web app
+    #[arg(long, env = "BTYPERDB_DIST")]
+    pub dist: Option<String>,
+
+    /// Token required by the admin endpoints. Admin endpoints are disabled if not set
+    #[arg(long, env = "BTYPERDB_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+
+    /// Check the store for updates every given number of seconds, and reload if changed
+    #[arg(long, env = "BTYPERDB_WATCH")]
+    pub watch: Option<u64>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// What to do instead of serving
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check that the stores are consistent, then exit. Exits with an error if problems are found
+    Check,
+}
+
+
+////////////////////////////////////////////////////////////
+/// One database release, served from its own store
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReleaseConfig {
+    pub name: String,
+    pub store: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Content of config.json. All fields are optional, as they can be given on the command line.
+/// Either a single store, or a list of releases (oldest first) can be given
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub store: Option<String>,
+    pub releases: Vec<ReleaseConfig>,
+    pub default_release: Option<String>,
+    pub bind: Option<String>,
+    pub dist: Option<String>,
+    pub admin_token: Option<String>,
+    pub watch: Option<u64>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Final server configuration
+#[derive(Debug)]
+pub struct ServerConfig {
+    pub releases: Vec<ReleaseConfig>,
+    pub default_release: String,
+    pub bind: String,
+    pub dist: PathBuf,
+    pub admin_token: Option<String>,
+    pub watch_interval: Option<u64>,
+}
+impl ServerConfig {
+
+    ////////////////////////////////////////////////////////////
+    /// Combine config file and command line arguments. Returns all problems found.
+    /// Bind address, web app and store layout are only checked if the server is to be started
+    pub fn from_args(args: &Args, serving: bool) -> Result<ServerConfig, Vec<String>> {
+        let mut errors = Vec::new();
+
+        //The config file can be skipped if everything is given on the command line
+        let config_file = if args.config.exists() {
+            match File::open(&args.config) {
+                Ok(f) => match serde_json::from_reader(BufReader::new(f)) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        errors.push(format!("Could not parse config file {}: {}", args.config.display(), e));
+                        ConfigFile::default()
+                    }
+                },
+                Err(e) => {
+                    errors.push(format!("Could not open config file {}: {}", args.config.display(), e));
+                    ConfigFile::default()
+                }
+            }
+        } else {
+            if args.store.is_none() || (serving && args.bind.is_none()) {
+                errors.push(format!("Config file {} does not exist", args.config.display()));
+            }
+            ConfigFile::default()
+        };
+
+        //A store on the command line replaces all releases in the config file, and with them the default release
+        let (releases, default_release) = if let Some(store) = args.store.clone().or(config_file.store) {
+            let releases = vec![ReleaseConfig {
+                name: "default".to_string(),
+                store,
+                description: String::new(),
+            }];
+            (releases, "default".to_string())
+        } else {
+            let default_release = config_file.default_release
+                .or(config_file.releases.last().map(|r| r.name.clone()))
+                .unwrap_or_default();
+            (config_file.releases, default_release)
+        };
+        let bind = args.bind.clone().or(config_file.bind);
+        let dist = args.dist.clone().or(config_file.dist).unwrap_or("./dist/".to_string());
+        let admin_token = args.admin_token.clone().or(config_file.admin_token);
+        let watch_interval = args.watch.or(config_file.watch);
+
+        if releases.is_empty() {
+            errors.push("No store given; set \"store\" or \"releases\" in the config file, --store or BTYPERDB_STORE".to_string());
+        } else if !releases.iter().any(|r| r.name == default_release) {
+            errors.push(format!("Default release {} is not among the releases", default_release));
+        }
+        if serving {
+            if bind.is_none() {
+                errors.push("No bind address given; set \"bind\" in the config file, --bind or BTYPERDB_BIND".to_string());
+            }
+            if !Path::new(&dist).is_dir() {
+                errors.push(format!("Web app directory {} does not exist", dist));
+            }
+
+            for r in &releases {
+                for e in check_store_layout(Path::new(&r.store)) {
+                    errors.push(format!("Release {}: {}", r.name, e));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ServerConfig {
+                releases,
+                default_release,
+                bind: bind.unwrap_or_default(),
+                dist: PathBuf::from(dist),
+                admin_token,
+                watch_interval,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Check that all files the server needs are in the store. Returns all problems found
+pub fn check_store_layout(path_store: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+    if !path_store.is_dir() {
+        errors.push(format!("Store {} is not a directory", path_store.display()));
+        return errors;
+    }
+
+    for f in ["tree.nwk", "meta/data.sqlite", "meta/btyperdb_include.tsv"].iter() {
+        let p = path_store.join(f);
+        if !p.is_file() {
+            errors.push(format!("Missing file {}", p.display()));
+        }
+    }
+
+    let p = path_store.join("fna");
+    if !p.is_dir() {
+        errors.push(format!("Missing directory {}", p.display()));
+    }
+    errors
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Arguments as if only a config file, and possibly a store, was given
+    fn args_with(config: PathBuf, store: Option<&str>) -> Args {
+        Args {
+            config,
+            store: store.map(|s| s.to_string()),
+            bind: None,
+            dist: None,
+            admin_token: None,
+            watch: None,
+            command: Some(Command::Check),
+        }
+    }
+
+    #[test]
+    fn store_override_replaces_default_release() {
+        let path = std::env::temp_dir().join(format!("btyperdb_config_test_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{
+            "releases": [{"name": "2024", "store": "/data/2024"}, {"name": "2025", "store": "/data/2025"}],
+            "default_release": "2025"
+        }"#).unwrap();
+
+        let config = ServerConfig::from_args(&args_with(path.clone(), None), false).unwrap();
+        assert_eq!(config.releases.len(), 2);
+        assert_eq!(config.default_release, "2025");
+
+        let config = ServerConfig::from_args(&args_with(path.clone(), Some("/data/other")), false).unwrap();
+        assert_eq!(config.releases.len(), 1);
+        assert_eq!(config.releases[0].store, "/data/other");
+        assert_eq!(config.default_release, "default");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use actix_web::{post, web, web::Data, HttpResponse};
+use rusqlite::{Connection, Result};
+
+use my_web_app::{CrossTab, CrossTabRequest};
+
+use crate::straintable::build_straindb_where;
+use crate::{ReleaseQuery, ServerState};
+
+
+/// Largest number of rows or columns kept; the rest are summed into "Other"
+const MAX_CATEGORIES: usize = 40;
+
+
+// curl --header "Content-Type: application/json" --request POST -d '{"row_column":"GTDB_Species","col_column":"Source_1","search":null}' 127.0.0.1:8080/crosstab
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: contingency table of two categorical columns
+#[post("/crosstab")]
+pub async fn crosstab(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<CrossTabRequest>) -> actix_web::Result<HttpResponse> {
+    let server_data = server_state.snapshot(&release)?;
+    let req = req_body.into_inner();
+
+    //Only known columns can be used, as names go into the query
+    for col in [&req.row_column, &req.col_column].iter() {
+        if !server_data.db_metadata.columns.contains_key(*col) {
+            return Ok(HttpResponse::BadRequest().body(format!("No such column: {}", col)));
+        }
+    }
+
+    let filter = match &req.search {
+        Some(search) => build_straindb_where(search),
+        None => String::new(),
+    };
+    let conn = server_data.conn.lock().unwrap();
+    match query_crosstab(&conn, &req.row_column, &req.col_column, &filter) {
+        Ok(table) => Ok(HttpResponse::Ok().json(table)),
+        Err(e) => Ok(HttpResponse::BadRequest().body(format!("Could not read database: {}", e))),
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Count strains for each pair of values of two columns
+pub fn query_crosstab(
+    conn: &Connection,
+    row_column: &str,
+    col_column: &str,
+    filter: &str
+) -> Result<CrossTab> {
+
+    let mut stmt = conn.prepare(format!(
+        "SELECT CAST(`{}` AS TEXT) as r, CAST(`{}` AS TEXT) as c, count(*) as cnt FROM straindata {} group by r, c",
+        row_column, col_column, filter
+    ).as_str())?;
+
+    let rows = stmt.query_map([], |row| {
+        let r: Option<String> = row.get(0)?;
+        let c: Option<String> = row.get(1)?;
+        let cnt: i32 = row.get(2)?;
+        Ok((r.unwrap_or_default(), c.unwrap_or_default(), cnt))
+    })?;
+    let mut list_counts = Vec::new();
+    for r in rows {
+        list_counts.push(r?);
+    }
+
+    //Decide on rows and columns; the largest first
+    let pick_names = |totals: HashMap<String, i32>| -> Vec<String> {
+        let mut names: Vec<(String, i32)> = totals.into_iter().collect();
+        names.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let has_other = names.len() > MAX_CATEGORIES;
+        let mut names: Vec<String> = names.into_iter().take(MAX_CATEGORIES).map(|(n, _)| n).collect();
+        if has_other {
+            names.push("Other".to_string());
+        }
+        names
+    };
+    let mut row_totals = HashMap::new();
+    let mut col_totals = HashMap::new();
+    for (r, c, cnt) in &list_counts {
+        *row_totals.entry(r.clone()).or_insert(0) += cnt;
+        *col_totals.entry(c.clone()).or_insert(0) += cnt;
+    }
+    let row_names = pick_names(row_totals);
+    let col_names = pick_names(col_totals);
+
+    //Fill in the table. Values not kept end up in "Other", which is last
+    let index = |names: &[String], name: &str| names.iter().position(|n| n == name).unwrap_or(names.len() - 1);
+    let mut counts = vec![vec![0; col_names.len()]; row_names.len()];
+    for (r, c, cnt) in list_counts {
+        counts[index(&row_names, &r)][index(&col_names, &c)] += cnt;
+    }
+
+    Ok(CrossTab {
+        row_column: row_column.to_string(),
+        col_column: col_column.to_string(),
+        row_names,
+        col_names,
+        counts,
+    })
+}
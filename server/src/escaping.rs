@@ -1,9 +1,14 @@
+use my_web_app::is_iso_date;
+
 
 ////////////////////////////////////////////////////////////
 /// x
 pub fn sql_stringarg_to_num(s: &String) -> String {
     if s.parse::<f64>().is_ok() {
         s.clone()
+    } else if is_iso_date(s) {
+        //Dates are stored as text and compare correctly as such
+        format!("'{}'", s)
     } else {
         panic!("bad value")
     }
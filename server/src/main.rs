@@ -3,20 +3,38 @@ pub mod stats;
 pub mod escaping;
 pub mod straintable;
 pub mod tree;
+pub mod sketch;
+pub mod config;
+pub mod reload;
+pub mod releases;
+pub mod check;
+pub mod crosstab;
+pub mod pointmap;
 
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock};
 use std::io::BufReader;
 
 use actix_files::Files;
 use actix_web::{web, web::Data, App, HttpResponse, HttpServer};
 use rusqlite::OpenFlags;
 use rusqlite::{Connection};
+use clap::Parser;
 use serde::Deserialize;
-use serde::Serialize;
 
-use my_web_app::{DatabaseMetadata, TreeData};
+use my_web_app::{DatabaseMetadata, ReleaseList, TreeData};
+use my_web_app::newick::parse_newick;
+use my_web_app::treelayout::TreeLayoutData;
+use check::*;
+use config::*;
+use crosstab::*;
+use pointmap::*;
+use reload::*;
+use releases::*;
+use sketch::*;
+use stats::*;
 use straintable::*;
 use tree::*;
 use zip::*;
@@ -24,24 +42,167 @@ use zip::*;
 
 
 ////////////////////////////////////////////////////////////
-/// Backend state
+/// One snapshot of the database, tree and column metadata
 pub struct ServerData {
-    conn: Connection,
+    conn: Mutex<Connection>,
     db_metadata: DatabaseMetadata,
     path_store: PathBuf,
     tree: TreeData,
+    tree_layout: Vec<u8>,
+    stats_config: StatsConfig,
+    sketches: Arc<RwLock<SketchIndex>>,
+}
+impl ServerData {
+
+    ////////////////////////////////////////////////////////////
+    /// Read everything from the store
+    pub fn load(path_store: &Path, sketches: Arc<RwLock<SketchIndex>>) -> Result<ServerData, String> {
+
+        //Read tree
+        let tree_str = std::fs::read_to_string(path_store.join("tree.nwk")).map_err(|e| format!("Could not read tree.nwk: {}", e))?;
+
+        //Lay out the tree once, instead of in every browser
+        let tree_layout = TreeLayoutData::from_tree(
+            &parse_newick(&tree_str).map_err(|e| format!("Could not parse tree.nwk: {}", e))?
+        ).to_bytes();
+        let tree = TreeData {
+            tree_str
+        };
+
+        // Open SQL database
+        let path_sql = path_store.join(Path::new("meta/data.sqlite"));
+        let conn = Connection::open_with_flags(&path_sql, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Could not open SQL database {}: {}", path_sql.display(), e))?;
+
+        let path_meta = path_store.join(Path::new("meta/btyperdb_include.tsv"));
+        let f_meta = File::open(&path_meta).map_err(|e| format!("Could not open btyperdb_include: {}", e))?;
+        let reader = BufReader::new(f_meta);
+        let stats_config = StatsConfig::read(path_store)?;
+        let db_metadata = read_database_metadata(
+            reader,
+            &conn,
+            &stats_config
+        ).map_err(|e| format!("Failed to read database meta from {}: {}", path_meta.display(), e))?;
+
+        Ok(ServerData {
+            conn: Mutex::new(conn),
+            db_metadata: db_metadata,
+            tree: tree,
+            tree_layout: tree_layout,
+            stats_config: stats_config,
+            path_store: path_store.into(),
+            sketches: sketches,
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// One database release. The current snapshot can be swapped while requests are running;
+/// these keep using the snapshot they started with
+pub struct ReleaseState {
+    name: String,
+    description: String,
+    current: RwLock<Arc<ServerData>>,
+    /// Sketch builder of the store; each snapshot sends it its own index to fill
+    sketcher: Mutex<Sender<Arc<RwLock<SketchIndex>>>>,
+}
+impl ReleaseState {
+
+    ////////////////////////////////////////////////////////////
+    /// Load a release from its store
+    pub fn load(release: &ReleaseConfig) -> Result<ReleaseState, String> {
+        let path_store = Path::new(&release.store);
+
+        // Sketch all genomes in the background
+        let sketcher = spawn_sketch_builder(path_store.into());
+        let sketches = Arc::new(RwLock::new(SketchIndex::new()));
+
+        let server_data = ServerData::load(path_store, sketches.clone())?;
+        sketcher.send(sketches).map_err(|e| format!("Sketch builder is not running: {}", e))?;
+        Ok(ReleaseState {
+            name: release.name.clone(),
+            description: release.description.clone(),
+            current: RwLock::new(Arc::new(server_data)),
+            sketcher: Mutex::new(sketcher),
+        })
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Get the current snapshot
+    pub fn snapshot(&self) -> Arc<ServerData> {
+        self.current.read().unwrap().clone()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Read the store again and swap in the new snapshot. The old one is kept on failure
+    pub fn reload(&self) -> Result<(), String> {
+        let old = self.snapshot();
+
+        //The old sketches are used until the builder has caught up with the store
+        let sketches = Arc::new(RwLock::new(old.sketches.read().unwrap().placeholder()));
+        let new = ServerData::load(&old.path_store, sketches.clone())?;
+        self.sketcher.lock().unwrap().send(sketches).map_err(|e| format!("Sketch builder is not running: {}", e))?;
+        *self.current.write().unwrap() = Arc::new(new);
+        println!("reloaded release {} from {}", self.name, old.path_store.display());
+        Ok(())
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Which release a request is about. The default release is used if not given
+#[derive(Debug, Deserialize)]
+pub struct ReleaseQuery {
+    pub release: Option<String>,
 }
 
 
 ////////////////////////////////////////////////////////////
 /// Backend state
-#[derive(Debug, Deserialize, Serialize)]
-struct ConfigFile {
-    store: String,
-    bind: String,
+pub struct ServerState {
+    releases: Vec<ReleaseState>,
+    default_release: String,
+    admin_token: Option<String>,
+    /// Releases and what changed between them. Comparing releases scans all strains, so it is only done on (re)load
+    release_list: RwLock<ReleaseList>,
 }
+impl ServerState {
 
+    ////////////////////////////////////////////////////////////
+    /// Compare the current snapshots of all releases again
+    pub fn update_release_list(&self) -> Result<(), String> {
+        let list = list_releases(self).map_err(|e| format!("Could not compare releases: {}", e))?;
+        *self.release_list.write().unwrap() = list;
+        Ok(())
+    }
 
+    ////////////////////////////////////////////////////////////
+    /// Reload the store of a release, and update the list of releases to match
+    pub fn reload(&self, name: &Option<String>) -> Result<(), String> {
+        let release = self.get_release(name).map_err(|e| e.to_string())?;
+        release.reload()?;
+        self.update_release_list()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Get a release by name; empty name means the default release
+    pub fn get_release(&self, name: &Option<String>) -> actix_web::Result<&ReleaseState> {
+        let name = match name {
+            Some(name) if name != "" => name,
+            _ => &self.default_release,
+        };
+        self.releases.iter()
+            .find(|r| &r.name == name)
+            .ok_or_else(|| actix_web::error::ErrorNotFound(format!("No such release: {}", name)))
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Get the current snapshot of the release asked for
+    pub fn snapshot(&self, query: &ReleaseQuery) -> actix_web::Result<Arc<ServerData>> {
+        Ok(self.get_release(&query.release)?.snapshot())
+    }
+}
 
 
 
@@ -53,54 +214,77 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
     env_logger::init();
 
-    // Read the config file
-    let f_meta = File::open("config.json").expect("Could not open config.json");
-    let config_reader = BufReader::new(f_meta);
-    let config_file:ConfigFile = serde_json::from_reader(config_reader).expect("Could not open config file");
-    let path_store = Path::new(&config_file.store);
-
-    //Read tree
-    let tree_str = std::fs::read_to_string(path_store.join("tree.nwk"))?;
-    let tree = TreeData {
-        tree_str
+    // Read the config file and command line; report all problems at once
+    let args = Args::parse();
+    let serving = args.command.is_none();
+    let config = match ServerConfig::from_args(&args, serving) {
+        Ok(config) => config,
+        Err(errors) => {
+            if serving {
+                eprintln!("Cannot start server:");
+            } else {
+                eprintln!("Invalid configuration:");
+            }
+            for e in errors {
+                eprintln!("  {}", e);
+            }
+            std::process::exit(1);
+        }
     };
 
-    // Open SQL database
-    let path_sql = path_store.join(Path::new("meta/data.sqlite"));
-    //let path = "/Users/mahogny/Desktop/rust/2_actix-yew-template/minimal_testing/meta/data.sqlite";
-    let conn = Connection::open_with_flags(&path_sql, OpenFlags::SQLITE_OPEN_READ_ONLY).expect("Could not open SQL database");
-
-    let path_meta = path_store.join(Path::new("meta/btyperdb_include.tsv"));
-    let f_meta = File::open(path_meta).expect("Could not open btyperdb_include");
-    let reader = BufReader::new(f_meta);
-    let db_metadata = read_database_metadata(
-        reader,
-        &conn
-    ).expect("Failed to read database meta");
-
-    let data = Data::new(Mutex::new(
-        ServerData {
-            conn: conn,
-            db_metadata: db_metadata,
-            tree: tree,
-            path_store: path_store.into()
-        }
-    ));
+    // Only check the stores if asked to
+    if let Some(Command::Check) = args.command {
+        let ok = check_releases(&config.releases);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Load all releases
+    let mut releases = Vec::new();
+    for r in &config.releases {
+        releases.push(ReleaseState::load(r).expect(&format!("Failed to load release {}", r.name)));
+    }
+    let data = Data::new(ServerState {
+        releases,
+        default_release: config.default_release.clone(),
+        admin_token: config.admin_token.clone(),
+        release_list: RwLock::new(ReleaseList {
+            default_release: config.default_release.clone(),
+            releases: Vec::new(),
+        }),
+    });
+    data.update_release_list().expect("Failed to compare releases");
+
+    // Optionally reload whenever the store is updated
+    if let Some(interval) = config.watch_interval {
+        spawn_store_watcher(data.clone(), interval);
+    }
 
+    let dist = config.dist.clone();
     HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
+            .app_data(web::PayloadConfig::new(100_000_000)) // uploaded genomes
             .wrap(actix_web::middleware::Logger::default())  //for debugging
             .service(straindata)
+            .service(straindata_by_id)
             .service(strainmeta)
+            .service(searchstats)
+            .service(crosstab)
+            .service(pointmap)
             .service(strainfasta)
             .service(treedata)
-            .service(Files::new("/", "./dist/").index_file("index.html"))
+            .service(treelayout)
+            .service(columnvalues)
+            .service(sketchsearch)
+            .service(distmatrix)
+            .service(admin_reload)
+            .service(releaselist)
+            .service(Files::new("/", &dist).index_file("index.html"))
             .default_service(
                 web::route().to(|| HttpResponse::NotFound()),  //header("Location", "/").finish()
             )
     })
-    .bind(config_file.bind)? /////////////// for dev, "127.0.0.1:8080"  ; 127.0.0.1:5199 for beagle deployment
+    .bind(&config.bind)? /////////////// for dev, "127.0.0.1:8080"  ; 127.0.0.1:5199 for beagle deployment
     .run()
     .await
 }
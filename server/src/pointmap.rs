@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use actix_web::{post, web, web::Data, HttpResponse};
+use rusqlite::{Connection, Result};
+
+use my_web_app::{DatabaseColumn, LocationColumns, MapPoint, PointMap, PointMapRequest};
+
+use crate::straintable::build_straindb_where;
+use crate::{ReleaseQuery, ServerState};
+
+
+// curl --header "Content-Type: application/json" --request POST -d '{"color_column":"GTDB_Species","search":null}' 127.0.0.1:8080/pointmap
+
+
+////////////////////////////////////////////////////////////
+/// Find numeric columns named like latitude and longitude
+pub fn detect_location_columns(columns: &BTreeMap<String, DatabaseColumn>) -> Option<LocationColumns> {
+    let find = |names: &[&str]| {
+        columns.values()
+            .find(|c| c.column_type.is_numeric() && names.contains(&c.column_id.to_lowercase().as_str()))
+            .map(|c| c.column_id.clone())
+    };
+    let latitude = find(&["latitude", "lat"])?;
+    let longitude = find(&["longitude", "lon", "lng", "long"])?;
+    Some(LocationColumns {
+        latitude,
+        longitude,
+    })
+}
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: isolate locations
+#[post("/pointmap")]
+pub async fn pointmap(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<PointMapRequest>) -> actix_web::Result<HttpResponse> {
+    let server_data = server_state.snapshot(&release)?;
+    let req = req_body.into_inner();
+
+    let location = match &server_data.db_metadata.location {
+        Some(location) => location,
+        None => return Ok(HttpResponse::BadRequest().body("This database has no isolate locations")),
+    };
+
+    //Only known columns can be used, as names go into the query
+    if !req.color_column.is_empty() && !server_data.db_metadata.columns.contains_key(&req.color_column) {
+        return Ok(HttpResponse::BadRequest().body(format!("No such column: {}", req.color_column)));
+    }
+
+    let filter = match &req.search {
+        Some(search) => build_straindb_where(search),
+        None => String::new(),
+    };
+    let conn = server_data.conn.lock().unwrap();
+    match query_pointmap(&conn, location, &req.color_column, &filter) {
+        Ok(points) => Ok(HttpResponse::Ok().json(points)),
+        Err(e) => Ok(HttpResponse::BadRequest().body(format!("Could not read database: {}", e))),
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Get coordinates, and the value to color by, of all strains having a location
+pub fn query_pointmap(
+    conn: &Connection,
+    location: &LocationColumns,
+    color_column: &str,
+    filter: &str
+) -> Result<PointMap> {
+
+    let value = if color_column.is_empty() {
+        "''".to_string()
+    } else {
+        format!("CAST(`{}` AS TEXT)", color_column)
+    };
+    let mut stmt = conn.prepare(format!(
+        "SELECT BTyperDB_ID, `{}`, `{}`, {} FROM straindata {}",
+        location.latitude, location.longitude, value, filter
+    ).as_str())?;
+
+    let rows = stmt.query_map([], |row| {
+        let id: String = row.get(0)?;
+        let lat: Option<f64> = row.get(1).ok().flatten();
+        let lon: Option<f64> = row.get(2).ok().flatten();
+        let value: Option<String> = row.get(3)?;
+        Ok((id, lat, lon, value))
+    })?;
+
+    let mut points = Vec::new();
+    for r in rows {
+        let (id, lat, lon, value) = r?;
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            if lat.abs() <= 90.0 && lon.abs() <= 180.0 {
+                points.push(MapPoint {
+                    id,
+                    lat: lat as f32,
+                    lon: lon as f32,
+                    value: value.unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    Ok(PointMap {
+        color_column: color_column.to_string(),
+        points,
+    })
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+
+use actix_web::{get, web::Data, HttpResponse};
+use rusqlite::{Connection, Result};
+
+use my_web_app::{ReleaseDiff, ReleaseInfo, ReleaseList};
+
+use crate::{ServerData, ServerState};
+
+
+////////////////////////////////////////////////////////////
+/// All strain IDs in a database
+fn query_strain_ids(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT BTyperDB_ID FROM straindata")?;
+    let ids = stmt.query_map([], |row| row.get(0))?;
+    let mut out = HashSet::new();
+    for id in ids {
+        if let Ok(id) = id {
+            out.insert(id);
+        }
+    }
+    Ok(out)
+}
+
+
+////////////////////////////////////////////////////////////
+/// Compare two releases
+fn compare_releases(
+    previous_name: &String,
+    previous: &ServerData,
+    previous_ids: &HashSet<String>,
+    current: &ServerData,
+    current_ids: &HashSet<String>,
+) -> ReleaseDiff {
+    let prev_cols: HashSet<&String> = previous.db_metadata.columns.keys().collect();
+    let cur_cols: HashSet<&String> = current.db_metadata.columns.keys().collect();
+
+    let mut columns_added: Vec<String> = cur_cols.difference(&prev_cols).map(|s| s.to_string()).collect();
+    let mut columns_removed: Vec<String> = prev_cols.difference(&cur_cols).map(|s| s.to_string()).collect();
+    columns_added.sort();
+    columns_removed.sort();
+
+    ReleaseDiff {
+        previous: previous_name.clone(),
+        strains_added: current_ids.difference(previous_ids).count() as i32,
+        strains_removed: previous_ids.difference(current_ids).count() as i32,
+        columns_added,
+        columns_removed,
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// List all releases, and what changed compared to the release before. This scans all strains
+/// of every release; the server keeps the result until a release is reloaded
+pub fn list_releases(server_state: &ServerState) -> Result<ReleaseList> {
+    let mut releases = Vec::new();
+
+    let mut previous: Option<(String, std::sync::Arc<ServerData>, HashSet<String>)> = None;
+    for r in &server_state.releases {
+        let data = r.snapshot();
+        let ids = query_strain_ids(&data.conn.lock().unwrap())?;
+
+        let diff = previous.as_ref().map(|(prev_name, prev_data, prev_ids)| {
+            compare_releases(prev_name, prev_data, prev_ids, &data, &ids)
+        });
+
+        releases.push(ReleaseInfo {
+            name: r.name.clone(),
+            description: r.description.clone(),
+            num_strain: data.db_metadata.num_strain,
+            diff,
+        });
+        previous = Some((r.name.clone(), data, ids));
+    }
+
+    Ok(ReleaseList {
+        default_release: server_state.default_release.clone(),
+        releases,
+    })
+}
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: list of releases served
+#[get("/releases")]
+pub async fn releaselist(server_state: Data<ServerState>) -> actix_web::Result<HttpResponse> {
+    let list = server_state.release_list.read().unwrap().clone();
+    Ok(HttpResponse::Ok().json(list))
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use actix_web::{post, web, web::Data, HttpRequest, HttpResponse};
+
+use crate::{ReleaseQuery, ServerState};
+use crate::stats::STATS_CONFIG;
+
+
+/// Files that make up one snapshot of the store
+const WATCHED_FILES: [&str; 4] = ["tree.nwk", "meta/data.sqlite", "meta/btyperdb_include.tsv", STATS_CONFIG];
+
+
+// curl --request POST --header "Authorization: Bearer TOKEN" 127.0.0.1:8080/admin/reload?release=2025
+
+////////////////////////////////////////////////////////////
+/// REST entry point: reload the store of a release without restarting the server
+#[post("/admin/reload")]
+pub async fn admin_reload(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req: HttpRequest) -> actix_web::Result<HttpResponse> {
+
+    //Only allowed with the right token
+    let given_token = req.headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match (&server_state.admin_token, given_token) {
+        (Some(token), Some(given)) if token == given => {},
+        _ => return Ok(HttpResponse::Forbidden().body("Invalid admin token")),
+    }
+
+    //Check that the release exists before starting
+    server_state.get_release(&release.release)?;
+
+    let state = server_state.clone();
+    let res = web::block(move || state.reload(&release.release)).await?;
+    match res {
+        Ok(()) => Ok(HttpResponse::Ok().body("Reloaded")),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Latest modification time of the watched files
+fn latest_modification(path_store: &Path) -> Option<SystemTime> {
+    WATCHED_FILES.iter()
+        .filter_map(|f| std::fs::metadata(path_store.join(f)).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+
+////////////////////////////////////////////////////////////
+/// Poll the store of each release for changes, and reload when any of the watched files is updated
+pub fn spawn_store_watcher(server_state: Data<ServerState>, interval_sec: u64) {
+    std::thread::spawn(move || {
+        let list_store: Vec<PathBuf> = server_state.releases.iter().map(|r| r.snapshot().path_store.clone()).collect();
+        let mut last_modified: Vec<Option<SystemTime>> = list_store.iter().map(|p| latest_modification(p)).collect();
+        loop {
+            std::thread::sleep(Duration::from_secs(interval_sec));
+            for (i, release) in server_state.releases.iter().enumerate() {
+                let modified = latest_modification(&list_store[i]);
+                if modified != last_modified[i] {
+                    //Give the publisher time to finish copying
+                    std::thread::sleep(Duration::from_secs(interval_sec));
+                    last_modified[i] = latest_modification(&list_store[i]);
+                    if let Err(e) = server_state.reload(&Some(release.name.clone())) {
+                        println!("failed to reload release {}: {}", release.name, e);
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,737 @@
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, RwLock};
+
+use actix_web::web::Json;
+use actix_web::{post, web, web::Data, HttpResponse};
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+
+use my_web_app::{DistanceMatrix, SketchHit, SketchSearchResult, StrainRequest};
+
+use crate::{ReleaseQuery, ServerState};
+
+
+/// k-mer size used for all sketches
+pub const SKETCH_K: usize = 21;
+
+/// Number of hashes kept per genome (bottom-s sketch)
+pub const SKETCH_SIZE: usize = 1000;
+
+/// Name of the sketch cache, relative to the store
+pub const SKETCH_CACHE: &str = "meta/sketches.bin";
+
+/// Largest FASTA accepted, after decompression
+pub const MAX_FASTA_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest number of hits returned by a sketch search
+pub const MAX_SEARCH_HITS: usize = 500;
+
+
+////////////////////////////////////////////////////////////
+/// MinHash sketch of one genome. Hashes are sorted, smallest first
+#[derive(Debug, Clone)]
+pub struct Sketch {
+    pub hashes: Vec<u64>,
+}
+impl Sketch {
+
+    ////////////////////////////////////////////////////////////
+    /// Sketch all sequences in a FASTA file. Accepts both plain and gzipped FASTA.
+    /// Fails if the file cannot be decompressed, is too large or holds no k-mers
+    pub fn from_fasta(data: &[u8]) -> Result<Sketch, String> {
+        if is_gzip(data) {
+            Sketch::from_reader(MultiGzDecoder::new(data), MAX_FASTA_BYTES)
+        } else {
+            Sketch::from_reader(data, MAX_FASTA_BYTES)
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Sketch a FASTA file on disk, plain or gzipped
+    pub fn from_fasta_file(path: &Path) -> Result<Sketch, String> {
+        let mut reader = File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let gzipped = is_gzip(reader.fill_buf().map_err(|e| format!("Could not read {}: {}", path.display(), e))?);
+        if gzipped {
+            Sketch::from_reader(MultiGzDecoder::new(reader), MAX_FASTA_BYTES)
+        } else {
+            Sketch::from_reader(reader, MAX_FASTA_BYTES)
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Sketch FASTA as it is read, without keeping it in memory. Fails after max_bytes,
+    /// so that a small gzip bomb cannot keep the server busy
+    fn from_reader(reader: impl Read, max_bytes: u64) -> Result<Sketch, String> {
+        let mut builder = SketchBuilder::new();
+        let mut reader = reader.take(max_bytes + 1);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut total = 0;
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Could not read FASTA: {}", e)),
+            };
+            total += n as u64;
+            if total > max_bytes {
+                return Err(format!("FASTA is larger than {} MB", max_bytes / 1024 / 1024));
+            }
+            builder.add_fasta(&buf[..n]);
+        }
+        let sketch = builder.finish();
+        if sketch.hashes.is_empty() {
+            return Err("No sequence found; expected FASTA".to_string());
+        }
+        Ok(sketch)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Compare two sketches. Returns (number of shared hashes, size of the merged sketch)
+    pub fn shared_hashes(&self, other: &Sketch) -> (u32, u32) {
+        let mut i = 0;
+        let mut j = 0;
+        let mut shared = 0;
+        let mut total = 0;
+
+        //Walk the union of both sorted lists, keeping only the smallest SKETCH_SIZE hashes
+        while total < SKETCH_SIZE && i < self.hashes.len() && j < other.hashes.len() {
+            let a = self.hashes[i];
+            let b = other.hashes[j];
+            if a == b {
+                shared += 1;
+                i += 1;
+                j += 1;
+            } else if a < b {
+                i += 1;
+            } else {
+                j += 1;
+            }
+            total += 1;
+        }
+        (shared, total as u32)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Mash distance between two sketches
+    pub fn mash_distance(&self, other: &Sketch) -> (f64, u32, u32) {
+        let (shared, total) = self.shared_hashes(other);
+        let dist = if shared == 0 || total == 0 {
+            1.0
+        } else {
+            let jaccard = shared as f64 / total as f64;
+            let d = -1.0 / (SKETCH_K as f64) * (2.0 * jaccard / (1.0 + jaccard)).ln();
+            d.min(1.0)
+        };
+        (dist, shared, total)
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Accumulates the smallest hashes of all canonical k-mers. FASTA can be added in chunks of any size;
+/// the current k-mer and line are carried over from one chunk to the next
+struct SketchBuilder {
+    heap: BinaryHeap<u64>,
+    in_heap: HashSet<u64>,
+    fwd: u64,
+    rev: u64,
+    /// Number of bases in a row so far; a k-mer is complete at SKETCH_K
+    valid: usize,
+    line_start: bool,
+    in_header: bool,
+}
+impl SketchBuilder {
+
+    fn new() -> SketchBuilder {
+        SketchBuilder {
+            heap: BinaryHeap::with_capacity(SKETCH_SIZE + 1),
+            in_heap: HashSet::with_capacity(SKETCH_SIZE + 1),
+            fwd: 0,
+            rev: 0,
+            valid: 0,
+            line_start: true,
+            in_header: false,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Add one hash, keeping only the SKETCH_SIZE smallest
+    fn add_hash(&mut self, h: u64) {
+        if self.heap.len() >= SKETCH_SIZE && h >= *self.heap.peek().unwrap() {
+            return;
+        }
+        if self.in_heap.insert(h) {
+            self.heap.push(h);
+            if self.heap.len() > SKETCH_SIZE {
+                let removed = self.heap.pop().unwrap();
+                self.in_heap.remove(&removed);
+            }
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Add the next part of a FASTA file. Each record is hashed separately
+    fn add_fasta(&mut self, content: &[u8]) {
+        let mask: u64 = (1 << (2 * SKETCH_K)) - 1;
+        let shift = 2 * (SKETCH_K - 1);
+
+        for c in content {
+            if *c == b'\n' {
+                self.line_start = true;
+                self.in_header = false;
+                continue;
+            }
+            if self.line_start && *c == b'>' {
+                //New record; k-mers do not span records
+                self.in_header = true;
+                self.valid = 0;
+            }
+            self.line_start = false;
+            if self.in_header {
+                continue;
+            }
+
+            let code = match *c {
+                b'A' | b'a' => 0,
+                b'C' | b'c' => 1,
+                b'G' | b'g' => 2,
+                b'T' | b't' => 3,
+                b'\r' => continue,
+                _ => {
+                    self.valid = 0;
+                    continue;
+                }
+            };
+            self.fwd = ((self.fwd << 2) | code) & mask;
+            self.rev = (self.rev >> 2) | ((3 - code) << shift);
+            self.valid += 1;
+            if self.valid >= SKETCH_K {
+                let canonical = if self.fwd < self.rev { self.fwd } else { self.rev };
+                self.add_hash(hash_kmer(canonical));
+            }
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Produce the final sorted sketch
+    fn finish(self) -> Sketch {
+        Sketch {
+            hashes: self.heap.into_sorted_vec(),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Does the data start like a gzip file?
+fn is_gzip(data: &[u8]) -> bool {
+    data.len() > 2 && data[0] == 0x1f && data[1] == 0x8b
+}
+
+
+////////////////////////////////////////////////////////////
+/// Mix the bits of a 2-bit encoded k-mer (splitmix64 finalizer)
+fn hash_kmer(kmer: u64) -> u64 {
+    let mut z = kmer.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Sketches of all genomes in the store. Filled in by a background thread
+pub struct SketchIndex {
+    pub sketches: Arc<Vec<(String, Sketch)>>,
+    pub ready: bool,
+}
+impl SketchIndex {
+
+    pub fn new() -> SketchIndex {
+        SketchIndex {
+            sketches: Arc::new(Vec::new()),
+            ready: false,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Index to use until a new one has been built. Shares the sketches of this one, which are cheap to copy
+    pub fn placeholder(&self) -> SketchIndex {
+        SketchIndex {
+            sketches: self.sketches.clone(),
+            ready: self.ready,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Find the closest genomes to a sketch, best first
+    pub fn nearest(&self, query: &Sketch, num_hits: usize) -> Vec<SketchHit> {
+        let mut hits: Vec<SketchHit> = self.sketches.iter().map(|(id, sketch)| {
+            let (dist, shared, total) = query.mash_distance(sketch);
+            SketchHit {
+                id: id.clone(),
+                distance: dist,
+                ani: 100.0 * (1.0 - dist),
+                shared_hashes: shared,
+                sketch_size: total,
+            }
+        }).collect();
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        hits.truncate(num_hits);
+        hits
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Get the sketch of one genome
+    pub fn get(&self, id: &str) -> Option<&Sketch> {
+        self.sketches.iter().find(|(n, _)| n == id).map(|(_, s)| s)
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Pairwise Mash distances between given strains. Strains without a sketch are reported as missing
+    pub fn distance_matrix(&self, list_id: &[String]) -> DistanceMatrix {
+        let mut names = Vec::new();
+        let mut list_sketch = Vec::new();
+        let mut missing = Vec::new();
+        for id in list_id {
+            if let Some(sketch) = self.get(id) {
+                names.push(id.clone());
+                list_sketch.push(sketch);
+            } else {
+                missing.push(id.clone());
+            }
+        }
+
+        let n = list_sketch.len();
+        let mut distance = vec![vec![0.0; n]; n];
+        for (i, a) in list_sketch.iter().enumerate() {
+            for (j, b) in list_sketch.iter().enumerate().skip(i + 1) {
+                let (dist, _, _) = a.mash_distance(b);
+                distance[i][j] = dist;
+                distance[j][i] = dist;
+            }
+        }
+
+        let order = cluster_order(&distance);
+        DistanceMatrix {
+            names,
+            distance,
+            order,
+            missing,
+        }
+    }
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Order of leaves after average linkage (UPGMA) clustering of a distance matrix
+fn cluster_order(distance: &[Vec<f64>]) -> Vec<usize> {
+
+    //Each cluster: leaves in display order, and distances to other clusters
+    let mut clusters: Vec<Vec<usize>> = (0..distance.len()).map(|i| vec![i]).collect();
+    let mut dist = distance.to_vec();
+
+    while clusters.len() > 1 {
+
+        //Find the closest pair of clusters
+        let mut best = (0, 1);
+        let mut best_dist = f64::MAX;
+        for (i, row) in dist.iter().enumerate() {
+            for (j, d) in row.iter().enumerate().skip(i + 1) {
+                if *d < best_dist {
+                    best_dist = *d;
+                    best = (i, j);
+                }
+            }
+        }
+        let (a, b) = best;
+
+        //Distance from the merged cluster to all others, weighted by cluster size
+        let size_a = clusters[a].len() as f64;
+        let size_b = clusters[b].len() as f64;
+        let merged: Vec<f64> = dist[a].iter().zip(dist[b].iter())
+            .map(|(da, db)| (da * size_a + db * size_b) / (size_a + size_b))
+            .collect();
+        for (k, d) in merged.into_iter().enumerate() {
+            dist[a][k] = d;
+            dist[k][a] = d;
+        }
+        dist[a][a] = 0.0;
+
+        //Merge b into a
+        let leaves_b = clusters.remove(b);
+        clusters[a].extend(leaves_b);
+        dist.remove(b);
+        for row in dist.iter_mut() {
+            row.remove(b);
+        }
+    }
+
+    clusters.pop().unwrap_or_default()
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Read the sketch cache. Format: repeated [id length u32, id, num hashes u32, hashes u64], little endian.
+/// Records are read up to the last complete one; the second value is false if anything followed it,
+/// such as a record cut short when the server was stopped while sketching
+fn read_sketch_cache(path: &Path) -> std::io::Result<(Vec<(String, Sketch)>, bool)> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), true)),
+        Err(e) => return Err(e),
+    };
+
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let read_u32 = |pos: usize| data.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    while pos < data.len() {
+        //Check that the whole record is there before taking it
+        let id_len = match read_u32(pos) {
+            Some(len) => len,
+            None => break,
+        };
+        let id_start = pos + 4;
+        let num_hashes = match read_u32(id_start + id_len) {
+            Some(n) => n,
+            None => break,
+        };
+        let hashes_start = id_start + id_len + 4;
+        let record_end = hashes_start + num_hashes * 8;
+        if record_end > data.len() {
+            break;
+        }
+
+        let id = String::from_utf8_lossy(&data[id_start..id_start + id_len]).to_string();
+        let hashes = data[hashes_start..record_end].chunks_exact(8)
+            .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect();
+        out.push((id, Sketch { hashes }));
+        pos = record_end;
+    }
+    Ok((out, pos == data.len()))
+}
+
+
+////////////////////////////////////////////////////////////
+/// Replace the sketch cache with the given sketches. Written to a temporary file first, so that the
+/// cache is never left half-written
+fn rewrite_sketch_cache(path: &Path, sketches: &Vec<(String, Sketch)>) -> std::io::Result<()> {
+    let path_tmp = path.with_extension("bin.tmp");
+    let mut writer = BufWriter::new(File::create(&path_tmp)?);
+    for (id, sketch) in sketches {
+        write_sketch(&mut writer, id, sketch)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&path_tmp, path)
+}
+
+
+////////////////////////////////////////////////////////////
+/// Append one sketch to the cache
+fn write_sketch(writer: &mut impl Write, id: &str, sketch: &Sketch) -> std::io::Result<()> {
+    writer.write_all(&(id.len() as u32).to_le_bytes())?;
+    writer.write_all(id.as_bytes())?;
+    writer.write_all(&(sketch.hashes.len() as u32).to_le_bytes())?;
+    for h in &sketch.hashes {
+        writer.write_all(&h.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+
+////////////////////////////////////////////////////////////
+/// Start the thread that sketches the genomes of a store. Each index sent to it is filled with
+/// cached sketches, plus sketches of genomes not cached yet, and then marked ready. Requests are
+/// handled one at a time, so only one thread ever sketches a store or writes its cache.
+/// Runs in its own thread as sketching the whole store takes a long time
+pub fn spawn_sketch_builder(path_store: PathBuf) -> Sender<Arc<RwLock<SketchIndex>>> {
+    let (tx, rx) = channel::<Arc<RwLock<SketchIndex>>>();
+    std::thread::spawn(move || {
+        for index in rx {
+            let sketches = build_sketches(&path_store);
+            let mut index = index.write().unwrap();
+            index.sketches = Arc::new(sketches);
+            index.ready = true;
+            println!("sketching done, {} genomes", index.sketches.len());
+        }
+    });
+    tx
+}
+
+
+////////////////////////////////////////////////////////////
+/// Sketches of all genomes in fna/. Cached sketches are reused; sketches of genomes no longer
+/// in the store are dropped from the cache
+fn build_sketches(path_store: &Path) -> Vec<(String, Sketch)> {
+    let path_cache = path_store.join(SKETCH_CACHE);
+
+    //Genomes in the store
+    let list_fna: Vec<(String, PathBuf)> = match std::fs::read_dir(path_store.join("fna")) {
+        Ok(list_files) => list_files.flatten()
+            .filter_map(|entry| {
+                let fname = entry.file_name().to_string_lossy().to_string();
+                fname.strip_suffix(".fna.gz").map(|id| (id.to_string(), entry.path()))
+            })
+            .collect(),
+        Err(e) => {
+            println!("could not list fna directory, {}", e);
+            Vec::new()
+        }
+    };
+    let in_store: HashSet<&String> = list_fna.iter().map(|(id, _)| id).collect();
+
+    let (cached, complete) = match read_sketch_cache(&path_cache) {
+        Ok(res) => res,
+        Err(e) => {
+            println!("could not read sketch cache, {}", e);
+            (Vec::new(), true)
+        },
+    };
+    let num_cached = cached.len();
+    println!("loaded {} sketches from cache", num_cached);
+
+    //Keep one sketch per genome still in the store
+    let mut known: HashSet<String> = HashSet::new();
+    let mut sketches: Vec<(String, Sketch)> = cached.into_iter()
+        .filter(|(id, _)| in_store.contains(id) && known.insert(id.clone()))
+        .collect();
+
+    //Cache is append-only, so a partially sketched store can be resumed. It is rewritten if it
+    //ends with an incomplete record, or has sketches to drop
+    if !complete || sketches.len() != num_cached {
+        println!("rewriting sketch cache with {} of {} sketches", sketches.len(), num_cached);
+        if let Err(e) = rewrite_sketch_cache(&path_cache, &sketches) {
+            println!("could not rewrite sketch cache, {}", e);
+        }
+    }
+    let mut cache_writer = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path_cache)
+        .map(BufWriter::new)
+        .map_err(|e| println!("sketch cache is not writable, {}", e))
+        .ok();
+
+    for (id, path) in &list_fna {
+        if known.contains(id) {
+            continue;
+        }
+        match Sketch::from_fasta_file(path) {
+            Ok(sketch) => {
+                //Flush each record, so that little is lost if the server is stopped
+                if let Some(w) = &mut cache_writer {
+                    if let Err(e) = write_sketch(w, id, &sketch).and_then(|_| w.flush()) {
+                        println!("could not write sketch cache, {}", e);
+                        cache_writer = None;
+                    }
+                }
+                known.insert(id.clone());
+                sketches.push((id.clone(), sketch));
+            },
+            Err(e) => {
+                println!("could not sketch {}: {}", path.display(), e);
+            }
+        }
+    }
+    sketches
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Query parameters for sketch search
+#[derive(Debug, Deserialize)]
+pub struct SketchSearchQuery {
+    pub n: Option<usize>,
+}
+
+
+// curl --data-binary @genome.fna 127.0.0.1:8080/sketchsearch?n=10
+
+////////////////////////////////////////////////////////////
+/// REST entry point: find the closest strains to an uploaded genome
+#[post("/sketchsearch")]
+pub async fn sketchsearch(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, query: web::Query<SketchSearchQuery>, body: web::Bytes) -> actix_web::Result<HttpResponse> {
+    let index = server_state.snapshot(&release)?.sketches.clone();
+    if !index.read().unwrap().ready {
+        return Ok(HttpResponse::ServiceUnavailable().body("Sketches of the database are still being computed"));
+    }
+
+    let num_hits = query.n.unwrap_or(20).min(MAX_SEARCH_HITS);
+    let res = web::block(move || {
+        let sketch = Sketch::from_fasta(&body)?;
+        Ok::<_, String>(index.read().unwrap().nearest(&sketch, num_hits))
+    }).await?;
+
+    match res {
+        Ok(hits) => Ok(HttpResponse::Ok().json(SketchSearchResult {
+            hits
+        })),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+
+
+// curl --header "Content-Type: application/json" --request POST  -d '{"list":["BTDB_2022-0001042.1","BTDB_2022-0001043.1"]}' 127.0.0.1:8080/distmatrix
+
+////////////////////////////////////////////////////////////
+/// REST entry point: pairwise distances between given strains
+#[post("/distmatrix")]
+pub async fn distmatrix(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<StrainRequest>) -> actix_web::Result<HttpResponse> {
+    let Json(req) = req_body;
+    let index = server_state.snapshot(&release)?.sketches.clone();
+    if !index.read().unwrap().ready {
+        return Ok(HttpResponse::ServiceUnavailable().body("Sketches of the database are still being computed"));
+    }
+
+    let matrix = web::block(move || {
+        index.read().unwrap().distance_matrix(&req.list)
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(matrix))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random DNA sequence, the same for a given seed
+    fn random_sequence(seed: u64, len: usize) -> String {
+        let mut state = seed;
+        (0..len).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            b"ACGT"[(state >> 62) as usize] as char
+        }).collect()
+    }
+
+    fn reverse_complement(seq: &str) -> String {
+        seq.chars().rev().map(|c| match c {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            _ => 'A',
+        }).collect()
+    }
+
+    #[test]
+    fn distance_of_identical_sketches() {
+        let seq = random_sequence(1, 20000);
+        let a = Sketch::from_fasta(format!(">a\n{}\n", seq).as_bytes()).unwrap();
+        let b = Sketch::from_fasta(format!(">b\n{}\n", reverse_complement(&seq)).as_bytes()).unwrap();
+        assert_eq!(a.hashes.len(), SKETCH_SIZE);
+        assert_eq!(a.mash_distance(&b), (0.0, SKETCH_SIZE as u32, SKETCH_SIZE as u32));
+    }
+
+    #[test]
+    fn distance_of_disjoint_sketches() {
+        let a = Sketch { hashes: (0..1000).collect() };
+        let b = Sketch { hashes: (1000..2000).collect() };
+        let (dist, shared, _) = a.mash_distance(&b);
+        assert_eq!(dist, 1.0);
+        assert_eq!(shared, 0);
+    }
+
+    #[test]
+    fn distance_grows_with_differences() {
+        let a = Sketch::from_fasta(format!(">a\n{}\n", random_sequence(1, 20000)).as_bytes()).unwrap();
+        let b = Sketch::from_fasta(format!(">b\n{}{}\n", random_sequence(1, 15000), random_sequence(2, 5000)).as_bytes()).unwrap();
+        let (dist, shared, _) = a.mash_distance(&b);
+        assert!(dist > 0.0 && dist < 1.0, "distance {}", dist);
+        assert!(shared > 0);
+    }
+
+    #[test]
+    fn rejects_fasta_without_sequence() {
+        assert!(Sketch::from_fasta(b"").is_err());
+        assert!(Sketch::from_fasta(b">empty\n").is_err());
+        assert!(Sketch::from_fasta(b"\x1f\x8bnot gzip").is_err());
+    }
+
+    #[test]
+    fn same_sketch_whatever_the_chunks() {
+        let fasta = format!(">a\n{}\n>b\n{}\n", random_sequence(1, 5000), random_sequence(2, 5000));
+        let whole = Sketch::from_fasta(fasta.as_bytes()).unwrap();
+        let mut builder = SketchBuilder::new();
+        for chunk in fasta.as_bytes().chunks(7) {
+            builder.add_fasta(chunk);
+        }
+        assert_eq!(builder.finish().hashes, whole.hashes);
+    }
+
+    #[test]
+    fn rejects_gzip_bomb() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        let line = [b'A'; 1024 * 1024];
+        for _ in 0..4 {
+            encoder.write_all(&line).unwrap();
+        }
+        let gz = encoder.finish().unwrap();
+        assert!(gz.len() < 64 * 1024);
+        assert!(Sketch::from_reader(MultiGzDecoder::new(gz.as_slice()), 2 * 1024 * 1024).unwrap_err().contains("larger"));
+        assert!(Sketch::from_reader(MultiGzDecoder::new(gz.as_slice()), 8 * 1024 * 1024).is_ok());
+    }
+
+    #[test]
+    fn cluster_order_keeps_close_leaves_together() {
+        let distance = vec![
+            vec![0.0, 0.9, 0.1, 0.8],
+            vec![0.9, 0.0, 0.8, 0.2],
+            vec![0.1, 0.8, 0.0, 0.9],
+            vec![0.8, 0.2, 0.9, 0.0],
+        ];
+        let order = cluster_order(&distance);
+        let pos = |i: usize| order.iter().position(|x| *x == i).unwrap() as i32;
+        assert_eq!(order.len(), 4);
+        assert_eq!((pos(0) - pos(2)).abs(), 1);
+        assert_eq!((pos(1) - pos(3)).abs(), 1);
+    }
+
+    #[test]
+    fn sketch_cache_with_truncated_record() {
+        let path = std::env::temp_dir().join(format!("sketch_cache_test_{}.bin", std::process::id()));
+        let sketches = vec![
+            ("g1".to_string(), Sketch { hashes: vec![1, 2, 3] }),
+            ("g2".to_string(), Sketch { hashes: vec![4, 5] }),
+        ];
+        rewrite_sketch_cache(&path, &sketches).unwrap();
+        let (read, complete) = read_sketch_cache(&path).unwrap();
+        assert!(complete);
+        assert_eq!(read.iter().map(|(id, s)| (id.as_str(), s.hashes.clone())).collect::<Vec<_>>(), vec![("g1", vec![1, 2, 3]), ("g2", vec![4, 5])]);
+
+        //Cut the last record short, as if the server stopped while writing it
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 3]).unwrap();
+        let (read, complete) = read_sketch_cache(&path).unwrap();
+        assert!(!complete);
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].0, "g1");
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read_sketch_cache(&path).unwrap().0.len(), 0);
+    }
+}
@@ -1,16 +1,125 @@
-use my_web_app::OneStats;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use my_web_app::{Binning, ChartType, LocationColumns, NumericHistogram, OneStats, TimeSeries};
 use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
 
 type DatabaseHistogram = Vec<(String,i32)>;
 
 
+/// Statistics definitions, relative to the store
+pub const STATS_CONFIG: &str = "meta/stats.json";
+
+
+////////////////////////////////////////////////////////////
+/// One chart on the statistics page
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StatsChartConfig {
+    pub title: String,
+    pub column: String,
+    #[serde(default)]
+    pub chart: ChartType,
+    /// Only keep the most common values
+    #[serde(default)]
+    pub top_n: Option<usize>,
+    /// Sum values not kept into an "Other" bar
+    #[serde(default = "default_true")]
+    pub other: bool,
+    /// For histograms: how to bin the values
+    #[serde(default)]
+    pub binning: Binning,
+    /// For histograms: number of bins
+    #[serde(default = "default_bins")]
+    pub bins: usize,
+    /// For time series: categorical column to stack counts by. top_n limits the number of stacked series
+    #[serde(default)]
+    pub stack_by: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_bins() -> usize {
+    20
+}
+
+
+////////////////////////////////////////////////////////////
+/// Content of meta/stats.json
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StatsConfig {
+    pub charts: Vec<StatsChartConfig>,
+    /// Columns with isolate coordinates. Detected from the column names if not given
+    #[serde(default)]
+    pub location: Option<LocationColumns>,
+}
+impl StatsConfig {
+
+    ////////////////////////////////////////////////////////////
+    /// Charts shown if the store has no stats.json
+    pub fn default_charts() -> StatsConfig {
+        let bar = |title: &str, column: &str| StatsChartConfig {
+            title: title.to_string(),
+            column: column.to_string(),
+            chart: ChartType::Bar,
+            top_n: None,
+            other: true,
+            binning: Binning::Fixed,
+            bins: default_bins(),
+            stack_by: None,
+        };
+        StatsConfig {
+            charts: vec![
+                StatsChartConfig {
+                    chart: ChartType::Map,
+                    ..bar("Number of genomes per country", "Country(Code)")
+                },
+                bar("BTyper3 Species", "matchcol_BTyper3_species"),
+                bar("GTDB Species", "GTDB_Species"),
+                bar("Isolation source (Source 1)", "Source_1"),
+                bar("Human Illness", "Human_Illness"),
+                bar("BTyper3 adjusted panC group", "BTyper3_Adjusted_panC_Group(predicted_species)"),
+            ],
+            location: None,
+        }
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Read the statistics definitions of a store, or use the defaults if there are none
+    pub fn read(path_store: &Path) -> std::result::Result<StatsConfig, String> {
+        let path = path_store.join(STATS_CONFIG);
+        if !path.exists() {
+            return Ok(StatsConfig::default_charts());
+        }
+        let f = File::open(&path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+        serde_json::from_reader(BufReader::new(f)).map_err(|e| format!("Could not parse {}: {}", path.display(), e))
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Table to compute statistics over: all strains, or only those passing a WHERE clause
+fn filtered_table(filter: &str) -> String {
+    if filter.trim() == "" {
+        "straindata".to_string()
+    } else {
+        format!("(SELECT * FROM straindata {})", filter)
+    }
+}
+
+
 ////////////////////////////////////////////////////////////
 /// 
 pub fn query_get_strain_count(
-    conn: &Connection
+    conn: &Connection,
+    filter: &str
 ) -> Result<i32> {
 
-    let mut stmt = conn.prepare("SELECT count(*) as cnt FROM straindata")?;
+    let mut stmt = conn.prepare(format!("SELECT count(*) as cnt FROM {}", filtered_table(filter)).as_str())?;
 
     let cnts = stmt.query_map([], |row| {
         let val = row.get(0)?;
@@ -31,10 +140,11 @@ pub fn query_get_strain_count(
 /// 
 pub fn query_histogram(
     conn: &Connection,
-    colname: &String
+    colname: &String,
+    filter: &str
 ) -> Result<DatabaseHistogram> {
 
-    let mut stmt = conn.prepare(format!("SELECT `{}` as grp, count(*) as cnt FROM straindata group by grp ORDER BY cnt DESC", colname).as_str())?; ////////// TODO: escape name of column?
+    let mut stmt = conn.prepare(format!("SELECT `{}` as grp, count(*) as cnt FROM {} group by grp ORDER BY cnt DESC", colname, filtered_table(filter)).as_str())?; ////////// TODO: escape name of column?
 
     let cnts = stmt.query_map([], |row| {
         let name:String = row.get(0)?;
@@ -57,20 +167,148 @@ pub fn query_histogram(
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// Compute one chart of the statistics page, over the strains passing the filter
 pub fn make_stats(
     conn: &Connection,
-    showname: &String,
-    colname: &String
+    chart: &StatsChartConfig,
+    filter: &str
 ) -> Result<OneStats> {
+    if chart.chart == ChartType::Histogram {
+        let values = query_numeric_values(conn, &chart.column, filter)?;
+        return Ok(OneStats {
+            name: chart.title.clone(),
+            chart: chart.chart,
+            column: chart.column.clone(),
+            hist: Vec::new(),
+            numeric: Some(NumericHistogram::from_values(values, chart.binning, chart.bins)),
+            timeseries: None,
+        });
+    }
+    if chart.chart == ChartType::Timeseries {
+        return Ok(OneStats {
+            name: chart.title.clone(),
+            chart: chart.chart,
+            column: chart.column.clone(),
+            hist: Vec::new(),
+            numeric: None,
+            timeseries: Some(query_timeseries(conn, chart, filter)?),
+        });
+    }
+
+    let mut hist = query_histogram(conn, &chart.column, filter)?;
+
+    //Only keep the most common values, which come first
+    if let Some(top_n) = chart.top_n {
+        if hist.len() > top_n {
+            let other_count: i32 = hist[top_n..].iter().map(|(_, cnt)| cnt).sum();
+            hist.truncate(top_n);
+            if chart.other {
+                hist.push(("Other".to_string(), other_count));
+            }
+        }
+    }
+
     Ok(OneStats {
-        name: showname.clone(),
-        hist: query_histogram(conn, colname)?,
+        name: chart.title.clone(),
+        chart: chart.chart,
+        column: chart.column.clone(),
+        hist: hist,
+        numeric: None,
+        timeseries: None,
+    })
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Count strains per year, optionally per value of another column. The year is taken from the
+/// first four characters, so the column can hold either years or ISO dates. Values not starting
+/// with a 4-digit year, such as 12/03/2019, are left out
+pub fn query_timeseries(
+    conn: &Connection,
+    chart: &StatsChartConfig,
+    filter: &str
+) -> Result<TimeSeries> {
+
+    let grp = match &chart.stack_by {
+        Some(stack_by) => format!("`{}`", stack_by),
+        None => "''".to_string(),
+    };
+    let mut stmt = conn.prepare(format!(
+        "SELECT CAST(substr(CAST(`{0}` AS TEXT),1,4) AS INTEGER) as yr, {1} as grp, count(*) as cnt FROM {2} WHERE CAST(`{0}` AS TEXT) GLOB '[0-9][0-9][0-9][0-9]*' group by yr, grp",
+        chart.column, grp, filtered_table(filter)
+    ).as_str())?;
+
+    let rows = stmt.query_map([], |row| {
+        let year: i32 = row.get(0)?;
+        let name: Option<String> = row.get(1)?;
+        let cnt: i32 = row.get(2)?;
+        Ok((year, name.unwrap_or_default(), cnt))
+    })?;
+
+    let mut list_counts = Vec::new();
+    for r in rows {
+        list_counts.push(r?);
+    }
+
+    //Keep the largest series, the rest go into "Other"
+    let mut totals: BTreeMap<String, i32> = BTreeMap::new();
+    for (_, name, cnt) in &list_counts {
+        *totals.entry(name.clone()).or_default() += cnt;
+    }
+    let mut names: Vec<(String, i32)> = totals.into_iter().collect();
+    names.sort_by(|a, b| b.1.cmp(&a.1));
+    let top_n = chart.top_n.unwrap_or(8);
+    let has_other = names.len() > top_n;
+    let mut series_names: Vec<String> = names.into_iter().take(top_n).map(|(name, _)| name).collect();
+    if has_other {
+        series_names.push("Other".to_string());
+    }
+
+    //One count per year, with no gaps between first and last year
+    let first_year = list_counts.iter().map(|(y, _, _)| *y).min().unwrap_or(0);
+    let last_year = list_counts.iter().map(|(y, _, _)| *y).max().unwrap_or(-1);
+    let years: Vec<i32> = (first_year..=last_year).collect();
+    let mut series: Vec<(String, Vec<i32>)> = series_names.iter().map(|name| (name.clone(), vec![0; years.len()])).collect();
+    for (year, name, cnt) in list_counts {
+        let i = series_names.iter().position(|n| n == &name).unwrap_or(series_names.len() - 1);
+        series[i].1[(year - first_year) as usize] += cnt;
+    }
+
+    Ok(TimeSeries {
+        years,
+        series,
     })
 }
 
 
 
+////////////////////////////////////////////////////////////
+/// Get all numeric values of a column. Missing and non-numeric values are left out
+pub fn query_numeric_values(
+    conn: &Connection,
+    colname: &String,
+    filter: &str
+) -> Result<Vec<f64>> {
+
+    let mut stmt = conn.prepare(format!("SELECT `{}` FROM {} WHERE typeof(`{}`) IN ('integer','real')", colname, filtered_table(filter), colname).as_str())?;
+
+    let values = stmt.query_map([], |row| {
+        let v: f64 = row.get(0)?;
+        Ok(v)
+    })?;
+
+    let mut outlist = Vec::new();
+    for v in values {
+        if let Ok(v) = v {
+            outlist.push(v);
+        }
+    }
+    Ok(outlist)
+}
+
+
+
 ////////////////////////////////////////////////////////////
 /// 
 pub fn query_dropdown(
@@ -78,7 +316,8 @@ pub fn query_dropdown(
     colname: &String
 ) -> Result<Vec<String>> {
 
-    let mut stmt = conn.prepare(format!("SELECT distinct `{}` as grp FROM straindata ORDER BY grp", colname).as_str())?; ////////// TODO: escape name of column?
+    //Read as text, as for crosstab, so that integer and boolean columns also get values. Sorted by the value as stored
+    let mut stmt = conn.prepare(format!("SELECT CAST(`{0}` AS TEXT) as grp FROM straindata WHERE `{0}` IS NOT NULL GROUP BY `{0}` ORDER BY `{0}`", colname).as_str())?; ////////// TODO: escape name of column?
 
     let cnts = stmt.query_map([], |row| {
         let name:String = row.get(0)?;
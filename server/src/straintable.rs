@@ -1,5 +1,4 @@
-use std::sync::Mutex;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Read;
 
 use actix_web::web::Json;
@@ -7,15 +6,18 @@ use actix_web::{get, post, web, web::Data, Responder};
 
 use my_web_app::{ComparisonType};
 use my_web_app::SearchSettings;
+use my_web_app::StrainRequest;
 use my_web_app::TableData;
-use my_web_app::DatabaseColumn;
+use my_web_app::read_database_columns;
 use my_web_app::DatabaseMetadata;
+use my_web_app::SearchStats;
 
 use rusqlite::types::ValueRef;
-use rusqlite::{Connection, Result};
+use rusqlite::{params_from_iter, Connection, Params, Result, Statement};
 
-use crate::ServerData;
+use crate::{ReleaseQuery, ServerData, ServerState};
 use crate::escaping::*;
+use crate::pointmap::detect_location_columns;
 use crate::stats::*;
 
 
@@ -26,12 +28,37 @@ use crate::stats::*;
 ////////////////////////////////////////////////////////////
 /// REST entry point
 #[post("/straindata")]
-async fn straindata(server_data: Data<Mutex<ServerData>>, req_body: web::Json<SearchSettings>) -> impl Responder {
+async fn straindata(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<SearchSettings>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
     //println!("{:?}",req_body); 
     let Json(search_settings) = req_body;
     let data = query_straintable(&server_data, search_settings).expect("could not read database");
     //info!("Data: {:?}", data);
-    serde_json::to_string(&data)
+    Ok(serde_json::to_string(&data))
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: strain table rows for a given list of strains
+#[post("/straindata_by_id")]
+async fn straindata_by_id(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<StrainRequest>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
+    let Json(req) = req_body;
+    let data = query_straintable_by_id(&server_data, &req.list).expect("could not read database");
+    Ok(serde_json::to_string(&data))
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: statistics page charts for the strains matching a search
+#[post("/searchstats")]
+async fn searchstats(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<SearchSettings>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
+    let Json(search_settings) = req_body;
+    let data = query_search_stats(&server_data, &search_settings).expect("could not read database");
+    Ok(serde_json::to_string(&data))
 }
 
 
@@ -39,10 +66,10 @@ async fn straindata(server_data: Data<Mutex<ServerData>>, req_body: web::Json<Se
 ////////////////////////////////////////////////////////////
 /// REST entry point
 #[get("/strainmeta")]
-async fn strainmeta(server_data: Data<Mutex<ServerData>>) -> impl Responder {
-    let server_data =server_data.lock().unwrap();
+async fn strainmeta(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
     //info!("metadata: {:?}", &server_data.db_metadata);
-    serde_json::to_string(&server_data.db_metadata)
+    Ok(serde_json::to_string(&server_data.db_metadata))
 }
 
 
@@ -51,6 +78,19 @@ async fn strainmeta(server_data: Data<Mutex<ServerData>>) -> impl Responder {
 /// x
 pub fn build_straindb_search(search: &SearchSettings) -> String {
     let mut query = "SELECT * FROM straindata ".to_string();
+    query.push_str(&build_straindb_where(search));
+    query.push_str(" limit 100000");
+
+    println!("search {}", query);
+    query
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// WHERE clause for the search criteria; empty if there are none
+pub fn build_straindb_where(search: &SearchSettings) -> String {
+    let mut query = String::new();
 
     //let mut list_params:Vec<String> = Vec::new();
     //https://docs.rs/rusqlite/latest/rusqlite/struct.Statement.html
@@ -74,9 +114,6 @@ pub fn build_straindb_search(search: &SearchSettings) -> String {
         //println!("{:?}",query);
         query.push_str(list_formatted_crit.join(" AND ").as_str());
     }
-    query.push_str(" limit 100000");
-
-    println!("search {}", query);
     query
 }
 
@@ -86,62 +123,35 @@ pub fn build_straindb_search(search: &SearchSettings) -> String {
 
 
 ////////////////////////////////////////////////////////////
-/// Get metadata about the database
+/// Get metadata about the database. Fails, rather than panics, on a bad column definition or query,
+/// so that a reload can keep the old snapshot
 pub fn read_database_metadata (
     src: impl Read,
-    conn: &Connection
-) -> Result<DatabaseMetadata> { 
+    conn: &Connection,
+    stats_config: &StatsConfig
+) -> std::result::Result<DatabaseMetadata, String> { 
 
     let mut list_dropdown = BTreeMap::new();
     let mut list_hist = Vec::new();
 
     /////////// Gather statistics to show
-    list_hist.push(make_stats(
-        &conn, 
-        &"BTyper3 Species".to_string(), 
-        &"matchcol_BTyper3_species".to_string())?);
-
-    list_hist.push(make_stats(
-        &conn, 
-        &"GTDB Species".to_string(), 
-        &"GTDB_Species".to_string())?);
-
-    list_hist.push(make_stats(
-        &conn, 
-        &"Isolation source (Source 1)".to_string(), 
-        &"Source_1".to_string())?);
-
-    list_hist.push(make_stats(
-        &conn, 
-        &"Human Illness".to_string(),
-        &"Human_Illness".to_string())?);
-
-    list_hist.push(make_stats(
-        &conn, 
-        &"BTyper3 adjusted panC group".to_string(), 
-        &"BTyper3_Adjusted_panC_Group(predicted_species)".to_string())?);
-
-
-    let hist_country = query_histogram(
-        &conn, 
-        &"Country(Code)".to_string())?;
-
+    for chart in &stats_config.charts {
+        list_hist.push(make_stats(&conn, chart, "").map_err(|e| format!("Could not compute chart {}: {}", chart.title, e))?);
+    }
 
-    let num_strain = query_get_strain_count(&conn).expect("Could not get SQL strain count");
+    let num_strain = query_get_strain_count(&conn, "").map_err(|e| format!("Could not get SQL strain count: {}", e))?;
 
 
     /////////// Other metadata from CSV-file
     let mut outlist = BTreeMap::new();
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .from_reader(src);
-    for result in reader.deserialize() {
-        let record: DatabaseColumn = result.unwrap();
+    for record in read_database_columns(src).map_err(|e| format!("Could not parse column definitions: {}", e))? {
 
         /////////// Drop-down values for relevant fields  --- detect from metadata file?
         if record.dropdown {
             //let col = record.column_id;//.to_string();
-            list_dropdown.insert(record.column_id.clone(), query_dropdown(conn, &record.column_id).expect("Failed to create dropdown"));        
+            let dropdown = query_dropdown(conn, &record.column_id)
+                .map_err(|e| format!("Failed to create dropdown for {}: {}", record.column_id, e))?;
+            list_dropdown.insert(record.column_id.clone(), dropdown);
         }
 
         outlist.insert(record.column_id.clone(), record);
@@ -151,35 +161,92 @@ pub fn read_database_metadata (
 //    println!("{:?}",list_dropdown);
 
     Ok(DatabaseMetadata {
-        columns: outlist,
         num_strain: num_strain,
+        location: stats_config.location.clone().or_else(|| detect_location_columns(&outlist)),
+        columns: outlist,
         column_dropdown: list_dropdown,
         list_hist: list_hist,
-        hist_country: hist_country,
     })
 }
 
 
 
 
+////////////////////////////////////////////////////////////
+/// Compute all charts of the statistics page, for the strains matching the search
+fn query_search_stats(
+    server_data: &ServerData,
+    search: &SearchSettings
+) -> Result<SearchStats> {
+    let filter = build_straindb_where(search);
+    let conn = server_data.conn.lock().unwrap();
+
+    let mut list_hist = Vec::new();
+    for chart in &server_data.stats_config.charts {
+        list_hist.push(make_stats(&conn, chart, &filter)?);
+    }
+    Ok(SearchStats {
+        num_strain: query_get_strain_count(&conn, &filter)?,
+        list_hist: list_hist,
+    })
+}
+
+
+
 ////////////////////////////////////////////////////////////
 /// Get entries from the strain table given search criteria
 fn query_straintable(
-    server_data: &Data<Mutex<ServerData>>,
+    server_data: &ServerData,
     search: SearchSettings
 ) -> Result<TableData> {
 
     let q = build_straindb_search(&search);
     //println!("Query database using: {}",q);
 
-    let server_data =server_data.lock().unwrap();
+    let conn = server_data.conn.lock().unwrap();
+
+    let stmt = conn.prepare(q.as_str())?;
+    query_table_rows(stmt, [])
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Get entries from the strain table given a list of strain IDs. Rows are returned in the order of the list
+fn query_straintable_by_id(
+    server_data: &ServerData,
+    list_id: &Vec<String>
+) -> Result<TableData> {
+
+    let placeholders = vec!["?"; list_id.len()].join(",");
+    let q = format!("SELECT * FROM straindata WHERE BTyperDB_ID IN ({})", placeholders);
+
+    let conn = server_data.conn.lock().unwrap();
+
+    let stmt = conn.prepare(q.as_str())?;
+    let mut data = query_table_rows(stmt, params_from_iter(list_id.iter()))?;
+
+    //Keep the order of the request
+    if let Some(col_id) = data.columns.iter().position(|c| c == "BTyperDB_ID") {
+        let order: HashMap<&String, usize> = list_id.iter().enumerate().map(|(i,id)| (id,i)).collect();
+        data.rows.sort_by_key(|row| order.get(&row[col_id]).copied().unwrap_or(usize::MAX));
+    }
+    Ok(data)
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Run a prepared query against the strain table and format all values as strings
+fn query_table_rows<P: Params>(
+    mut stmt: Statement,
+    params: P
+) -> Result<TableData> {
 
-    let mut stmt = server_data.conn.prepare(q.as_str())?;
-    
     let cn = stmt.column_names().iter().map(|x| x.to_string()).collect();
     let numcol = stmt.column_count();
 
-    let rows = stmt.query_map([], |row| {
+    let rows = stmt.query_map(params, |row| {
         let mut out:Vec<String> = Vec::new();
         for i in 0..numcol {
 
@@ -214,4 +281,3 @@ fn query_straintable(
         rows: ok_rows,
     })
 }
-
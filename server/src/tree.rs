@@ -1,15 +1,82 @@
-use std::sync::Mutex;
-use actix_web::{Responder, get, web::Data};
+use actix_web::{HttpResponse, Responder, get, post, web, web::Data};
+use rusqlite::{Connection, Result};
 
-use crate::ServerData;
+use my_web_app::{ColumnValues, ColumnValuesRequest};
+
+use crate::{ReleaseQuery, ServerState};
 
 
 ////////////////////////////////////////////////////////////
 /// REST entry point
 #[get("/treedata")]
-async fn treedata(server_data: Data<Mutex<ServerData>>) -> impl Responder {
-    let server_data =server_data.lock().unwrap();
+async fn treedata(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
     let data = &server_data.tree;
-    serde_json::to_string(&data)
+    Ok(serde_json::to_string(&data))
+}
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: tree layout computed at startup, in the binary format of TreeLayoutData
+#[get("/treelayout")]
+async fn treelayout(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>) -> actix_web::Result<impl Responder> {
+    let server_data = server_state.snapshot(&release)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(server_data.tree_layout.clone()))
 }
 
+
+// curl --header "Content-Type: application/json" --request POST -d '{"columns":["GTDB_Species"]}' 127.0.0.1:8080/columnvalues
+
+
+////////////////////////////////////////////////////////////
+/// REST entry point: values of some columns for all strains, to label and color the tree
+#[post("/columnvalues")]
+async fn columnvalues(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<ColumnValuesRequest>) -> actix_web::Result<HttpResponse> {
+    let server_data = server_state.snapshot(&release)?;
+    let req = req_body.into_inner();
+
+    //Only known columns can be used, as names go into the query
+    for col in &req.columns {
+        if !server_data.db_metadata.columns.contains_key(col) {
+            return Ok(HttpResponse::BadRequest().body(format!("No such column: {}", col)));
+        }
+    }
+
+    let conn = server_data.conn.lock().unwrap();
+    let values = query_column_values(&conn, &req.columns).expect("could not read database");
+    Ok(HttpResponse::Ok().json(values))
+}
+
+
+////////////////////////////////////////////////////////////
+/// Get values of columns for all strains, as text
+pub fn query_column_values(
+    conn: &Connection,
+    columns: &Vec<String>
+) -> Result<ColumnValues> {
+
+    let mut select = vec!["BTyperDB_ID".to_string()];
+    for col in columns {
+        select.push(format!("CAST(`{}` AS TEXT)", col));
+    }
+    let mut stmt = conn.prepare(format!("SELECT {} FROM straindata", select.join(", ")).as_str())?;
+
+    let mut ids = Vec::new();
+    let mut values = vec![Vec::new(); columns.len()];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        ids.push(row.get(0)?);
+        for (i, list) in values.iter_mut().enumerate() {
+            let v: Option<String> = row.get(i + 1)?;
+            list.push(v.unwrap_or_default());
+        }
+    }
+
+    Ok(ColumnValues {
+        columns: columns.clone(),
+        ids,
+        values,
+    })
+}
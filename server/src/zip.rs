@@ -1,4 +1,3 @@
-use std::sync::Mutex;
 
 use actix_web::http::header::ContentDisposition;
 use actix_web::web::Json;
@@ -14,14 +13,14 @@ use tokio_util::io::ReaderStream;
 
 use my_web_app::StrainRequest;
 
-use crate::ServerData;
+use crate::{ReleaseQuery, ServerState};
 use crate::escaping::*;
 
 
 ////////////////////////////////////////////////////////////
 /// x
 #[post("/strainfasta")]
-pub async fn strainfasta(server_data: Data<Mutex<ServerData>>, req_body: web::Json<StrainRequest>) -> HttpResponse {
+pub async fn strainfasta(server_state: Data<ServerState>, release: web::Query<ReleaseQuery>, req_body: web::Json<StrainRequest>) -> actix_web::Result<HttpResponse> {
     println!("{:?}",req_body); 
     let Json(req) = req_body;
 
@@ -35,10 +34,7 @@ pub async fn strainfasta(server_data: Data<Mutex<ServerData>>, req_body: web::Js
 
     //let list_files = vec!["BTDB_2022-0001042.1".to_string()];
 
-    let path_store = {
-    let server_data =server_data.lock().unwrap();
-        server_data.path_store.clone()
-    };
+    let path_store = server_state.snapshot(&release)?.path_store.clone();
     let path_fna = path_store.join("fna");
 
 
@@ -64,10 +60,10 @@ pub async fn strainfasta(server_data: Data<Mutex<ServerData>>, req_body: web::Js
         archive.finalize().await.unwrap();
     });
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .insert_header(("Content-Type", "application/zip"))
         .insert_header(ContentDisposition::attachment("btyper_fastq.zip"))
-        .streaming(ReaderStream::new(r))
+        .streaming(ReaderStream::new(r)))
  }
 
 
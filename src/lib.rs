@@ -1,6 +1,10 @@
-use std::collections::{BTreeMap};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 use serde::{Deserialize, Serialize, Serializer, de};
 
+pub mod newick;
+pub mod treelayout;
+
 type DatabaseHistogram = Vec<(String,i32)>;
 
 
@@ -17,7 +21,188 @@ pub struct TableData {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OneStats {
     pub name: String,
+    pub chart: ChartType,
+    /// Column the chart is computed from
+    #[serde(default)]
+    pub column: String,
     pub hist: DatabaseHistogram,
+    /// Only for numeric histograms
+    #[serde(default)]
+    pub numeric: Option<NumericHistogram>,
+    /// Only for time series
+    #[serde(default)]
+    pub timeseries: Option<TimeSeries>,
+}
+impl OneStats {
+
+    ////////////////////////////////////////////////////////////
+    /// Format the counts behind the chart as TSV
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::new();
+        if let Some(numeric) = &self.numeric {
+            tsv.push_str(&format!("{} from\tto\tcount\n", self.column));
+            for (i, cnt) in numeric.counts.iter().enumerate() {
+                tsv.push_str(&format!("{}\t{}\t{}\n", numeric.edges[i], numeric.edges[i+1], cnt));
+            }
+        } else if let Some(ts) = &self.timeseries {
+            tsv.push_str("year");
+            for (name, _) in &ts.series {
+                tsv.push_str(&format!("\t{}", name));
+            }
+            tsv.push('\n');
+            for (i, year) in ts.years.iter().enumerate() {
+                tsv.push_str(&format!("{}", year));
+                for (_, counts) in &ts.series {
+                    tsv.push_str(&format!("\t{}", counts[i]));
+                }
+                tsv.push('\n');
+            }
+        } else {
+            tsv.push_str(&format!("{}\tcount\n", self.column));
+            for (name, cnt) in &self.hist {
+                tsv.push_str(&format!("{}\t{}\n", name, cnt));
+            }
+        }
+        tsv
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Statistics page charts, computed for the strains matching a search
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchStats {
+    pub num_strain: i32,
+    pub list_hist: Vec<OneStats>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// How a statistic is drawn on the statistics page
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartType {
+    /// Horizontal bar of fractions
+    #[default]
+    Bar,
+    /// World map; the column holds ISO 3166 alpha-3 country codes
+    Map,
+    /// Histogram of a numeric column, binned on the server
+    Histogram,
+    /// Counts per year of a year or date column, optionally stacked by a categorical column
+    Timeseries,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Counts per year. Each series has one count per year; without stacking there is a single series
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TimeSeries {
+    pub years: Vec<i32>,
+    pub series: Vec<(String, Vec<i32>)>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// How numeric values are split into bins
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Binning {
+    /// Bins of equal width
+    #[default]
+    Fixed,
+    /// Bins of equal width on a log10 scale; only positive values are counted
+    Log,
+    /// Bins holding roughly the same number of values
+    Quantile,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Histogram of a numeric column, with summary statistics of all values
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct NumericHistogram {
+    pub binning: Binning,
+    /// Bin edges; bin i covers edges[i]..edges[i+1]
+    pub edges: Vec<f64>,
+    pub counts: Vec<i32>,
+    pub num_values: i32,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+}
+impl NumericHistogram {
+
+    ////////////////////////////////////////////////////////////
+    /// Bin the values into at most num_bins bins
+    pub fn from_values(mut values: Vec<f64>, binning: Binning, num_bins: usize) -> NumericHistogram {
+        values.retain(|v| v.is_finite());
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = values.len();
+        let (min, max, mean, median) = if n == 0 {
+            (0.0, 0.0, 0.0, 0.0)
+        } else {
+            let median = if n % 2 == 1 {
+                values[n / 2]
+            } else {
+                (values[n / 2 - 1] + values[n / 2]) / 2.0
+            };
+            (values[0], values[n - 1], values.iter().sum::<f64>() / n as f64, median)
+        };
+
+        let num_bins = num_bins.max(1);
+        let edges: Vec<f64> = if n == 0 {
+            Vec::new()
+        } else if min == max {
+            vec![min, max]
+        } else {
+            match binning {
+                Binning::Fixed => (0..=num_bins)
+                    .map(|i| min + (max - min) * i as f64 / num_bins as f64)
+                    .collect(),
+                Binning::Log => {
+                    let min_pos = values.iter().copied().find(|v| *v > 0.0).unwrap_or(1.0);
+                    let (lmin, lmax) = (min_pos.log10(), max.max(min_pos).log10());
+                    (0..=num_bins)
+                        .map(|i| 10f64.powf(lmin + (lmax - lmin) * i as f64 / num_bins as f64))
+                        .collect()
+                },
+                Binning::Quantile => {
+                    let mut edges: Vec<f64> = (0..num_bins)
+                        .map(|i| values[i * n / num_bins])
+                        .collect();
+                    edges.push(max);
+                    edges.dedup();
+                    edges
+                },
+            }
+        };
+
+        //Count values per bin. The last bin includes its upper edge
+        let mut counts = vec![0; edges.len().saturating_sub(1)];
+        if !counts.is_empty() {
+            for v in &values {
+                if binning == Binning::Log && *v <= 0.0 {
+                    continue;
+                }
+                let i = edges.partition_point(|e| e <= v).saturating_sub(1).min(counts.len() - 1);
+                counts[i] += 1;
+            }
+        }
+
+        NumericHistogram {
+            binning,
+            edges,
+            counts,
+            num_values: n as i32,
+            min,
+            max,
+            mean,
+            median,
+        }
+    }
 }
 
 
@@ -30,7 +215,15 @@ pub struct DatabaseMetadata {
     pub column_dropdown: BTreeMap<String, Vec<String>>,
 
     pub list_hist: Vec<OneStats>,
-    pub hist_country: DatabaseHistogram,   
+
+    /// Columns with isolate coordinates, if the store has them
+    #[serde(default)]
+    pub location: Option<LocationColumns>,
+}
+impl Default for DatabaseMetadata {
+    fn default() -> Self {
+        DatabaseMetadata::new()
+    }
 }
 impl DatabaseMetadata {
 
@@ -42,20 +235,36 @@ impl DatabaseMetadata {
             columns: BTreeMap::new(),
             column_dropdown: BTreeMap::new(),
             list_hist: Vec::new(),
-            hist_country: Vec::new(),
+            location: None,
         }
     }
 
 
+    ////////////////////////////////////////////////////////////
+    /// Columns passing the filter, grouped by category. Categories and columns are sorted by their display names
+    pub fn columns_by_category(&self, filter: impl Fn(&DatabaseColumn) -> bool) -> Vec<(String, Vec<&DatabaseColumn>)> {
+        let mut groups: BTreeMap<String, Vec<&DatabaseColumn>> = BTreeMap::new();
+        for col in self.columns.values().filter(|c| filter(c)) {
+            groups.entry(col.display_category()).or_default().push(col);
+        }
+        let mut out: Vec<(String, Vec<&DatabaseColumn>)> = groups.into_iter().collect();
+        for (_, list) in out.iter_mut() {
+            list.sort_by_key(|c| c.display_name());
+        }
+        out
+    }
+
+
     ////////////////////////////////////////////////////////////
     /// Set up default search criteria
     pub fn make_default_search(&self) -> SearchSettings {
         
-        let mut list_default = Vec::new();
-        list_default.push("CheckM_Completeness".to_string());
-        list_default.push("CheckM_Contamination".to_string());
-        list_default.push("Quast_N50".to_string());
-        list_default.push("Kraken_Phylum(Bacillota)".to_string());
+        let list_default = vec![
+            "CheckM_Completeness".to_string(),
+            "CheckM_Contamination".to_string(),
+            "Quast_N50".to_string(),
+            "Kraken_Phylum(Bacillota)".to_string(),
+        ];
 
         let mut list_fields=Vec::new();
         for v in &list_default {
@@ -72,16 +281,80 @@ impl DatabaseMetadata {
 }
 
 
+////////////////////////////////////////////////////////////
+/// Type of data in a column, as given in btyperdb_include.tsv
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Float,
+    /// ISO 8601 date; YYYY, YYYY-MM or YYYY-MM-DD
+    Date,
+    /// Stored as 1/0
+    Boolean,
+}
+impl ColumnType {
+
+    ////////////////////////////////////////////////////////////
+    /// Name as used in btyperdb_include.tsv
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Text => "text",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::Boolean => "boolean",
+        }
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Type from its name in btyperdb_include.tsv
+    pub fn from_name(name: &str) -> Option<ColumnType> {
+        match name.trim().to_lowercase().as_str() {
+            "text" => Some(ColumnType::Text),
+            "integer" => Some(ColumnType::Integer),
+            "float" => Some(ColumnType::Float),
+            "date" => Some(ColumnType::Date),
+            "boolean" => Some(ColumnType::Boolean),
+            _ => None,
+        }
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Can values be compared as numbers?
+    pub fn is_numeric(&self) -> bool {
+        *self == ColumnType::Integer || *self == ColumnType::Float
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Is the string a date as YYYY, YYYY-MM or YYYY-MM-DD?
+pub fn is_iso_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    let lens_ok = match parts.len() {
+        1 => parts[0].len() == 4,
+        2 => parts[0].len() == 4 && parts[1].len() == 2,
+        3 => parts[0].len() == 4 && parts[1].len() == 2 && parts[2].len() == 2,
+        _ => false,
+    };
+    lens_ok && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+}
+
+
 ////////////////////////////////////////////////////////////
 /// Metadata about one column in the database
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct DatabaseColumn {
     pub column_id: String,
-    pub column_type: String,	
+    #[serde(deserialize_with = "deserialize_column_type")]
+    pub column_type: ColumnType,	
     pub default_v1: String,	
     pub default_v2: String,	
-    pub default_show_column: String,
 
+    #[serde(deserialize_with = "deserialize_01bool", serialize_with = "serialize_01bool")]
+    pub default_show_column: bool,
     #[serde(deserialize_with = "deserialize_01bool", serialize_with = "serialize_01bool")]
     pub dropdown: bool, 
     #[serde(deserialize_with = "deserialize_01bool", serialize_with = "serialize_01bool")]
@@ -92,11 +365,85 @@ pub struct DatabaseColumn {
     pub print: bool,
     
     pub notes: String,
+
+    /// Group of columns this one is shown under, e.g. "Assembly quality"
+    #[serde(default)]
+    pub category: String,
+    /// Unit of the values, e.g. "bp"
+    #[serde(default)]
+    pub unit: String,
+    /// Human-readable name; the column ID is used if empty
+    #[serde(default)]
+    pub label: String,
+    /// Longer description, shown as a tooltip
+    #[serde(default)]
+    pub description: String,
+}
+impl DatabaseColumn {
+
+    ////////////////////////////////////////////////////////////
+    /// Name to show to the user
+    pub fn display_name(&self) -> String {
+        if !self.label.is_empty() {
+            self.label.clone()
+        } else {
+            self.column_id.replace("_", " ")
+        }
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Name to show to the user, with the unit if there is one
+    pub fn display_name_with_unit(&self) -> String {
+        if !self.unit.is_empty() {
+            format!("{} ({})", self.display_name(), self.unit)
+        } else {
+            self.display_name()
+        }
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Category to group the column under
+    pub fn display_category(&self) -> String {
+        if !self.category.is_empty() {
+            self.category.clone()
+        } else {
+            "Other".to_string()
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Read column definitions from btyperdb_include.tsv
+pub fn read_database_columns(src: impl Read) -> Result<Vec<DatabaseColumn>, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(src);
+    let mut list_columns = Vec::new();
+    for result in reader.deserialize() {
+        list_columns.push(result?);
+    }
+    Ok(list_columns)
 }
 
 
 ////////////////////////////////////////////////////////////
-/// 1/0 => bool
+/// Name => ColumnType. Unknown types are read as text, with a warning, so that an old
+/// btyperdb_include.tsv can still be loaded
+fn deserialize_column_type<'de, D>(deserializer: D) -> Result<ColumnType, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let s: String = de::Deserialize::deserialize(deserializer)?;
+    Ok(ColumnType::from_name(&s).unwrap_or_else(|| {
+        println!("Warning: unknown column type '{}'; treated as text", s);
+        ColumnType::Text
+    }))
+}
+
+
+////////////////////////////////////////////////////////////
+/// 1/0 => bool. Empty means 0
 fn deserialize_01bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: de::Deserializer<'de>,
@@ -105,7 +452,7 @@ where
 
     match s {
         "1" => Ok(true),
-        "0" => Ok(false),
+        "0" | "" => Ok(false),
         _ => Err(de::Error::unknown_variant(s, &["1", "0"])),
     }
 }
@@ -128,7 +475,7 @@ where
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// List of strains to get data for
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct StrainRequest {
     pub list: Vec<String>
@@ -138,11 +485,16 @@ pub struct StrainRequest {
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// Criteria of a search; strains must meet all of them
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct SearchSettings {
     pub criteria: Vec<SearchCriteria>
 }
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings::new()
+    }
+}
 impl SearchSettings {
     pub fn new() -> SearchSettings {
 
@@ -152,11 +504,12 @@ impl SearchSettings {
         c.comparison = ComparisonType::Like("BTDB_2022-0000001.1".to_string());// "".to_string();
  */
         
-        let mut list_default = Vec::new();
-        list_default.push("CheckM_Completeness".to_string());
-        list_default.push("CheckM_Contamination".to_string());
-        list_default.push("Quast_N50".to_string());
-        list_default.push("Kraken_Phylum(Bacillota)".to_string());
+        let list_default = vec![
+            "CheckM_Completeness".to_string(),
+            "CheckM_Contamination".to_string(),
+            "Quast_N50".to_string(),
+            "Kraken_Phylum(Bacillota)".to_string(),
+        ];
 
         let mut list_fields=Vec::new();
         for v in list_default {
@@ -175,17 +528,22 @@ impl SearchSettings {
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// One search criterion: a column and how to compare it
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct SearchCriteria {
     pub field: String,
     pub comparison: ComparisonType,
 }
+impl Default for SearchCriteria {
+    fn default() -> Self {
+        SearchCriteria::new()
+    }
+}
 impl SearchCriteria {
 
 
     ////////////////////////////////////////////////////////////
-    /// 
+    /// Criterion on no column yet
     pub fn new() -> SearchCriteria {
         SearchCriteria {
             field: "".to_string(),
@@ -195,7 +553,7 @@ impl SearchCriteria {
 
 
     ////////////////////////////////////////////////////////////
-    /// 
+    /// Criterion with the default comparison of a column
     pub fn default_search(col: &DatabaseColumn) -> SearchCriteria {
         let comp = ComparisonType::default_comparison(col);
         SearchCriteria {
@@ -207,7 +565,7 @@ impl SearchCriteria {
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// How values of a column are compared
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub enum ComparisonType {
     Like(String),
@@ -219,17 +577,13 @@ impl ComparisonType {
     ////////////////////////////////////////////////////////////
     /// Generate a comparison with default fields
     pub fn default_comparison(db: &DatabaseColumn) -> ComparisonType {
-        if db.column_type == "text" {
-            ComparisonType::Like(db.default_v1.clone()) 
-        } else if db.column_type == "float" || db.column_type == "integer" {
-            ComparisonType::FromTo(
+        match db.column_type {
+            ColumnType::Text | ColumnType::Boolean => ComparisonType::Like(db.default_v1.clone()),
+            ColumnType::Integer | ColumnType::Float | ColumnType::Date => ComparisonType::FromTo(
                 db.default_v1.clone(),
                 db.default_v2.clone(),
-            ) 
-        } else {
-            println!("!!!! unexpected type of data {}", db.column_type);
-            ComparisonType::Like("".to_string()) //TODO
-        }        
+            ),
+        }
     }
 
 
@@ -239,7 +593,230 @@ impl ComparisonType {
 
 
 ////////////////////////////////////////////////////////////
-/// 
+/// One strain found by sketch similarity search
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SketchHit {
+    pub id: String,
+    pub distance: f64,
+    pub ani: f64,
+    pub shared_hashes: u32,
+    pub sketch_size: u32,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Result of sketch similarity search, closest strain first
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SketchSearchResult {
+    pub hits: Vec<SketchHit>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Pairwise Mash distances between strains. Order gives a clustering of the strains, for display
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DistanceMatrix {
+    pub names: Vec<String>,
+    pub distance: Vec<Vec<f64>>,
+    pub order: Vec<usize>,
+    pub missing: Vec<String>,
+}
+impl DistanceMatrix {
+
+    ////////////////////////////////////////////////////////////
+    /// Format as TSV, in clustered order
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::new();
+        tsv.push_str("strain");
+        for i in &self.order {
+            tsv.push('\t');
+            tsv.push_str(&self.names[*i]);
+        }
+        tsv.push('\n');
+        for i in &self.order {
+            tsv.push_str(&self.names[*i]);
+            for j in &self.order {
+                tsv.push_str(&format!("\t{}", self.distance[*i][*j]));
+            }
+            tsv.push('\n');
+        }
+        tsv
+    }
+}
+
+
+
+
+////////////////////////////////////////////////////////////
+/// Request for a cross-tabulation of two categorical columns, over all strains or a search
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CrossTabRequest {
+    pub row_column: String,
+    pub col_column: String,
+    pub search: Option<SearchSettings>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Contingency table of two categorical columns. Rows and columns are sorted by total count
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CrossTab {
+    pub row_column: String,
+    pub col_column: String,
+    pub row_names: Vec<String>,
+    pub col_names: Vec<String>,
+    pub counts: Vec<Vec<i32>>,
+}
+impl CrossTab {
+
+    ////////////////////////////////////////////////////////////
+    /// Total count of each row
+    pub fn row_totals(&self) -> Vec<i32> {
+        self.counts.iter().map(|r| r.iter().sum()).collect()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Total count of each column
+    pub fn col_totals(&self) -> Vec<i32> {
+        (0..self.col_names.len()).map(|j| self.counts.iter().map(|r| r[j]).sum()).collect()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Format counts as TSV, with row and column totals
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::new();
+        tsv.push_str(&format!("{} / {}", self.row_column, self.col_column));
+        for name in &self.col_names {
+            tsv.push('\t');
+            tsv.push_str(name);
+        }
+        tsv.push_str("\ttotal\n");
+        let row_totals = self.row_totals();
+        for (i, name) in self.row_names.iter().enumerate() {
+            tsv.push_str(name);
+            for c in &self.counts[i] {
+                tsv.push_str(&format!("\t{}", c));
+            }
+            tsv.push_str(&format!("\t{}\n", row_totals[i]));
+        }
+        tsv.push_str("total");
+        for c in self.col_totals() {
+            tsv.push_str(&format!("\t{}", c));
+        }
+        tsv.push_str(&format!("\t{}\n", row_totals.iter().sum::<i32>()));
+        tsv
+    }
+}
+
+
+
+
+////////////////////////////////////////////////////////////
+/// Request for the values of some columns, for all strains
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ColumnValuesRequest {
+    pub columns: Vec<String>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Values of some columns for all strains. values has one list per column, in the order of ids
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ColumnValues {
+    pub columns: Vec<String>,
+    pub ids: Vec<String>,
+    pub values: Vec<Vec<String>>,
+}
+impl ColumnValues {
+
+    ////////////////////////////////////////////////////////////
+    /// Position of each strain in the lists
+    pub fn index_by_id(&self) -> HashMap<String, usize> {
+        self.ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// All values of a column, if it was requested
+    pub fn column(&self, column: &str) -> Option<&Vec<String>> {
+        self.columns.iter().position(|c| c == column).map(|i| &self.values[i])
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Columns holding latitude and longitude of where a strain was isolated
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct LocationColumns {
+    pub latitude: String,
+    pub longitude: String,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Request for isolate locations, over all strains or a search. Points are labeled by color_column unless empty
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PointMapRequest {
+    pub color_column: String,
+    pub search: Option<SearchSettings>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Location of one strain
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MapPoint {
+    pub id: String,
+    pub lat: f32,
+    pub lon: f32,
+    pub value: String,
+}
+
+
+////////////////////////////////////////////////////////////
+/// Locations of all strains having coordinates
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PointMap {
+    pub color_column: String,
+    pub points: Vec<MapPoint>,
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// What changed in a release compared to the release before
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ReleaseDiff {
+    pub previous: String,
+    pub strains_added: i32,
+    pub strains_removed: i32,
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// One database release served
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ReleaseInfo {
+    pub name: String,
+    pub description: String,
+    pub num_strain: i32,
+    pub diff: Option<ReleaseDiff>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// All database releases served, oldest first
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ReleaseList {
+    pub default_release: String,
+    pub releases: Vec<ReleaseInfo>,
+}
+
+
+
+////////////////////////////////////////////////////////////
+/// Tree in Newick format
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TreeData {
     pub tree_str: String,
@@ -389,4 +966,21 @@ impl SerializeNode {
 
 }
 
-*/
\ No newline at end of file
+*/
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_column_type_is_text() {
+        let tsv = "column_id\tcolumn_type\tdefault_v1\tdefault_v2\tdefault_show_column\tdropdown\tdisplay\tsearch\tprint\tnotes\n\
+            Length\tInteger\t\t\t1\t0\t1\t1\t1\t\n\
+            Host\tstring\t\t\t0\t1\t1\t1\t1\t\n\
+            Source\t\t\t\t0\t1\t1\t1\t1\t\n";
+        let columns = read_database_columns(tsv.as_bytes()).unwrap();
+        let types: Vec<ColumnType> = columns.iter().map(|c| c.column_type).collect();
+        assert_eq!(types, vec![ColumnType::Integer, ColumnType::Text, ColumnType::Text]);
+    }
+}
@@ -0,0 +1,205 @@
+
+
+////////////////////////////////////////////////////////////
+/// One node of a Newick tree
+#[derive(Debug, Clone)]
+pub struct NewickNode {
+    pub name: Option<String>,
+    pub branch_length: f32,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+
+////////////////////////////////////////////////////////////
+/// A rooted tree parsed from a Newick string. Node IDs are indices into the node list
+#[derive(Debug, Clone)]
+pub struct NewickTree {
+    pub nodes: Vec<NewickNode>,
+    pub root: usize,
+}
+impl NewickTree {
+
+    ////////////////////////////////////////////////////////////
+    /// Is the node a tip?
+    pub fn is_leaf(&self, id: usize) -> bool {
+        self.nodes[id].children.is_empty()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Names of all tips, in the order of the Newick string
+    pub fn tip_names(&self) -> Vec<String> {
+        self.postorder()
+            .into_iter()
+            .filter(|id| self.is_leaf(*id))
+            .filter_map(|id| self.nodes[id].name.clone())
+            .collect()
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// All node IDs, children before parents
+    pub fn postorder(&self) -> Vec<usize> {
+        let mut out = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(self.root, false)];
+        while let Some((id, visited)) = stack.pop() {
+            if visited {
+                out.push(id);
+            } else {
+                stack.push((id, true));
+                for child in self.nodes[id].children.iter().rev() {
+                    stack.push((*child, false));
+                }
+            }
+        }
+        out
+    }
+
+    ////////////////////////////////////////////////////////////
+    /// Add a node and return its ID
+    fn add_node(&mut self, parent: Option<usize>) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(NewickNode {
+            name: None,
+            branch_length: 0.0,
+            parent,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+        id
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Parse a tree in Newick format. Written to handle trees with many thousands of tips quickly
+pub fn parse_newick(s: &str) -> Result<NewickTree, String> {
+    let mut tree = NewickTree {
+        nodes: Vec::new(),
+        root: 0,
+    };
+    let root = tree.add_node(None);
+    let mut current = root;
+
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '(' => {
+                current = tree.add_node(Some(current));
+                i += 1;
+            },
+            ',' => {
+                let parent = tree.nodes[current].parent.ok_or(format!("Unexpected , at position {}", i))?;
+                current = tree.add_node(Some(parent));
+                i += 1;
+            },
+            ')' => {
+                current = tree.nodes[current].parent.ok_or(format!("Unbalanced ) at position {}", i))?;
+                i += 1;
+            },
+            ':' => {
+                //Branch length
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && !",();[".contains(chars[i]) {
+                    i += 1;
+                }
+                let len_str: String = chars[start..i].iter().collect();
+                let len_str = len_str.trim();
+                if !len_str.is_empty() {
+                    tree.nodes[current].branch_length = len_str.parse::<f32>().map_err(|_| format!("Invalid branch length {} at position {}", len_str, start))?;
+                }
+            },
+            '[' => {
+                //Comment; skipped
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+                i += 1;
+            },
+            ';' => {
+                break;
+            },
+            '\'' => {
+                //Quoted label; '' is an escaped quote
+                let mut name = String::new();
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == '\'' {
+                        if i + 1 < chars.len() && chars[i+1] == '\'' {
+                            name.push('\'');
+                            i += 2;
+                            continue;
+                        }
+                        break;
+                    }
+                    name.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                tree.nodes[current].name = Some(name);
+            },
+            c if c.is_whitespace() => {
+                i += 1;
+            },
+            _ => {
+                //Unquoted label
+                let start = i;
+                while i < chars.len() && !",():;[".contains(chars[i]) && !chars[i].is_whitespace() {
+                    i += 1;
+                }
+                tree.nodes[current].name = Some(chars[start..i].iter().collect());
+            }
+        }
+    }
+
+    if current != root {
+        return Err("Unbalanced parentheses in tree".to_string());
+    }
+    Ok(tree)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_labels() {
+        let tree = parse_newick("('a b':1,'it''s (x)':2,c);").unwrap();
+        assert_eq!(tree.tip_names(), vec!["a b", "it's (x)", "c"]);
+        assert_eq!(tree.nodes[1].branch_length, 1.0);
+        assert_eq!(tree.nodes[2].branch_length, 2.0);
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let tree = parse_newick("(a[&date=2020]:1,[note, with (parens)]b:2)root[&&NHX];").unwrap();
+        assert_eq!(tree.tip_names(), vec!["a", "b"]);
+        assert_eq!(tree.nodes[tree.root].name.as_deref(), Some("root"));
+        assert_eq!(tree.nodes[1].branch_length, 1.0);
+    }
+
+    #[test]
+    fn nested_tree() {
+        let tree = parse_newick("((a:1,b:2)ab:0.5,c:3);").unwrap();
+        assert_eq!(tree.nodes.len(), 5);
+        assert_eq!(tree.tip_names(), vec!["a", "b", "c"]);
+        assert_eq!(*tree.postorder().last().unwrap(), tree.root);
+    }
+
+    #[test]
+    fn unbalanced_parentheses() {
+        assert!(parse_newick("((a,b),c;").is_err());
+        assert!(parse_newick("(a,b));").is_err());
+        assert!(parse_newick("a,b;").is_err());
+    }
+
+    #[test]
+    fn invalid_branch_length() {
+        assert!(parse_newick("(a:x,b);").is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::newick::NewickTree;
+
+
+/// First bytes of a binary tree layout, including a format version
+const LAYOUT_MAGIC: &[u8; 4] = b"BTL1";
+
+
+////////////////////////////////////////////////////////////
+/// Rectangular layout of a tree, computed once on the server and sent in binary form.
+/// Nodes are numbered in postorder, so children come before parents and the root is last
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeLayoutData {
+    /// Parent of each node; -1 for the root
+    pub parent: Vec<i32>,
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    /// Name of each node; empty if unnamed
+    pub names: Vec<String>,
+    /// Lines to draw, as x1,y1,x2,y2
+    pub vertex: Vec<f32>,
+    /// Node each line belongs to
+    pub owner: Vec<u32>,
+}
+impl TreeLayoutData {
+
+    ////////////////////////////////////////////////////////////
+    /// Lay out a tree. Tips are placed one unit apart in y; x is the distance from the root
+    pub fn from_tree(tree: &NewickTree) -> TreeLayoutData {
+
+        //Renumber nodes in postorder
+        let order = tree.postorder();
+        let mut new_id = vec![0; tree.nodes.len()];
+        for (i, old) in order.iter().enumerate() {
+            new_id[*old] = i;
+        }
+        let num_nodes = order.len();
+
+        let mut parent = Vec::with_capacity(num_nodes);
+        let mut names = Vec::with_capacity(num_nodes);
+        for old in &order {
+            let node = &tree.nodes[*old];
+            parent.push(node.parent.map(|p| new_id[p] as i32).unwrap_or(-1));
+            names.push(node.name.clone().unwrap_or_default());
+        }
+
+        //y-position: tips in order, parents at the average of their children
+        let mut y = vec![0.0; num_nodes];
+        let mut next_y = 0.0;
+        for (i, old) in order.iter().enumerate() {
+            let node = &tree.nodes[*old];
+            if node.children.is_empty() {
+                y[i] = next_y;
+                next_y += 1.0;
+            } else {
+                let sum_y: f32 = node.children.iter().map(|c| y[new_id[*c]]).sum();
+                y[i] = sum_y / (node.children.len() as f32);
+            }
+        }
+
+        //x-position: parents are handled before children when going backwards
+        let mut x = vec![0.0; num_nodes];
+        for i in (0..num_nodes).rev() {
+            if parent[i] >= 0 {
+                x[i] = x[parent[i] as usize] + tree.nodes[order[i]].branch_length;
+            }
+        }
+
+        //Lines: horizontal to each child, and one vertical spanning the children
+        let mut vertex = Vec::with_capacity(num_nodes * 8);
+        let mut owner = Vec::with_capacity(num_nodes * 2);
+        for (i, old) in order.iter().enumerate() {
+            let node = &tree.nodes[*old];
+            if node.children.is_empty() {
+                continue;
+            }
+            let mut child_min_y = f32::MAX;
+            let mut child_max_y = f32::MIN;
+            for c in &node.children {
+                let c = new_id[*c];
+                child_min_y = child_min_y.min(y[c]);
+                child_max_y = child_max_y.max(y[c]);
+                vertex.extend_from_slice(&[x[i], y[c], x[c], y[c]]);
+                owner.push(c as u32);
+            }
+            vertex.extend_from_slice(&[x[i], child_min_y, x[i], child_max_y]);
+            owner.push(i as u32);
+        }
+
+        TreeLayoutData {
+            parent,
+            x,
+            y,
+            names,
+            vertex,
+            owner,
+        }
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Number of lines to draw
+    pub fn num_lines(&self) -> usize {
+        self.owner.len()
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Encode as bytes. All numbers are little-endian; the arrays come first, then the names
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let num_nodes = self.parent.len();
+        let mut out = Vec::with_capacity(12 + num_nodes * 20 + self.num_lines() * 20);
+        out.extend_from_slice(LAYOUT_MAGIC);
+        out.extend_from_slice(&(num_nodes as u32).to_le_bytes());
+        out.extend_from_slice(&(self.num_lines() as u32).to_le_bytes());
+        for v in &self.parent {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in self.x.iter().chain(self.y.iter()) {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in &self.owner {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in &self.vertex {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        for name in &self.names {
+            out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+        }
+        out
+    }
+
+
+    ////////////////////////////////////////////////////////////
+    /// Decode from bytes made by to_bytes
+    pub fn from_bytes(data: &[u8]) -> Result<TreeLayoutData, String> {
+        let mut reader = ByteReader {
+            data,
+            pos: 0,
+        };
+        if reader.take(4)? != LAYOUT_MAGIC {
+            return Err("Not a tree layout, or of an unknown version".to_string());
+        }
+        let num_nodes = reader.read_u32()? as usize;
+        let num_lines = reader.read_u32()? as usize;
+
+        let parent = (0..num_nodes).map(|_| reader.read_u32().map(|v| v as i32)).collect::<Result<Vec<_>, _>>()?;
+        let x = (0..num_nodes).map(|_| reader.read_f32()).collect::<Result<Vec<_>, _>>()?;
+        let y = (0..num_nodes).map(|_| reader.read_f32()).collect::<Result<Vec<_>, _>>()?;
+        let owner = (0..num_lines).map(|_| reader.read_u32()).collect::<Result<Vec<_>, _>>()?;
+        let vertex = (0..num_lines * 4).map(|_| reader.read_f32()).collect::<Result<Vec<_>, _>>()?;
+        let mut names = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes {
+            let len = reader.read_u32()? as usize;
+            let name = std::str::from_utf8(reader.take(len)?).map_err(|e| format!("Bad node name: {}", e))?;
+            names.push(name.to_string());
+        }
+
+        Ok(TreeLayoutData {
+            parent,
+            x,
+            y,
+            names,
+            vertex,
+            owner,
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////
+/// Reads numbers from a byte array, in order
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> ByteReader<'a> {
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let out = self.data.get(self.pos..self.pos + n).ok_or("Tree layout is truncated")?;
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newick::parse_newick;
+
+    fn example_layout() -> TreeLayoutData {
+        let tree = parse_newick("((a:1,b:2)ab:0.5,'c d':3);").unwrap();
+        TreeLayoutData::from_tree(&tree)
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let lay = example_layout();
+        assert_eq!(lay.parent.len(), 5);
+        assert_eq!(TreeLayoutData::from_bytes(&lay.to_bytes()).unwrap(), lay);
+    }
+
+    #[test]
+    fn rejects_truncated_bytes() {
+        let bytes = example_layout().to_bytes();
+        for len in [0, 3, 8, 20, bytes.len() - 1] {
+            assert!(TreeLayoutData::from_bytes(&bytes[..len]).is_err(), "accepted {} of {} bytes", len, bytes.len());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_magic() {
+        let mut bytes = example_layout().to_bytes();
+        bytes[3] = b'9';
+        assert!(TreeLayoutData::from_bytes(&bytes).is_err());
+    }
+}